@@ -1,9 +1,27 @@
+pub const CONTRACT_VERSION_KEY: &[u8] = b"contractversion";
 pub const CONFIG_KEY: &[u8] = b"config";
 pub const REWARD_POOL_KEY: &[u8] = b"rewardpool";
 pub const VIEWING_KEY_KEY: &[u8] = b"viewingkey";
+pub const UNBONDINGS_KEY: &[u8] = b"unbondings";
+pub const COMPOUNDERS_KEY: &[u8] = b"compounders";
+pub const COMPOUNDER_INDICES_KEY: &[u8] = b"compounderindices";
+pub const REVOKED_PERMITS_KEY: &[u8] = b"revokedpermits";
+pub const ROLES_KEY: &[u8] = b"roles";
+pub const FUNDINGS_KEY: &[u8] = b"fundings";
+pub const ALLOWANCES_KEY: &[u8] = b"allowances";
+pub const RECEIVERS_KEY: &[u8] = b"receivers";
 
 pub const RESPONSE_BLOCK_SIZE: usize = 256;
 
-// TODO: get those as an input for specific coins, as some coins might require different scales than others
-pub const INC_TOKEN_SCALE: u128 = 1_000_000_000_000; // 10 ^ 12
-pub const REWARD_SCALE: u128 = 1_000_000_000_000; // 10 ^ 12
+// Bumped whenever the layout of the stored state changes, along with an upgrade in `migrate`. The first release
+// didn't store a version, and counts as version 0
+pub const CONTRACT_VERSION: u32 = 1;
+
+pub const BOOST_SCALE: u64 = 10_000; // A boost of 1x
+pub const COMPOUND_ALL_LIMIT: u32 = 50;
+pub const FUNDING_HISTORY_LIMIT: u32 = 50;
+// Every change to the pool walks all the schedules, so there can't be too many of them
+pub const MAX_REWARD_SCHEDULES: usize = 20;
+// Queries can't tell the current height, so rewards can be queried up to this many blocks after the pool's last
+// update. That's about a month, which is more than enough for any UI
+pub const MAX_REWARDS_QUERY_LOOKAHEAD: u64 = 500_000;
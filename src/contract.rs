@@ -1,6 +1,7 @@
 use cosmwasm_std::{
-    from_binary, to_binary, Api, Binary, CosmosMsg, Env, Extern, HandleResponse, HumanAddr,
-    InitResponse, Querier, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+    from_binary, log, to_binary, Api, Binary, CosmosMsg, Env, Extern, HandleResponse, HumanAddr,
+    InitResponse, LogAttribute, MigrateResponse, Querier, ReadonlyStorage, StdResult, Storage,
+    Uint128, WasmMsg,
 };
 use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
 use secret_toolkit::crypto::sha_256;
@@ -9,11 +10,19 @@ use secret_toolkit::storage::{TypedStore, TypedStoreMut};
 use secret_toolkit::utils::{pad_handle_result, pad_query_result};
 
 use crate::constants::*;
+use crate::error::ContractError;
+use crate::migrate::{upgrade_config, upgrade_reward_pool, upgrade_user, v0};
 use crate::msg::ResponseStatus::Success;
 use crate::msg::{
-    HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg, ReceiveAnswer, ReceiveMsg,
+    FundingInfo, HandleAnswer, HandleMsg, InitMsg, MigrateMsg, QueryAnswer, QueryMsg,
+    QueryWithPermit, ReceiveAnswer, ReceiveMsg, ReceiverHandleMsg, RewardAmount,
+    RewardScheduleInfo, RoleInfo, UnbondingInfo,
+};
+use crate::permit::{Permission, Permit};
+use crate::state::{
+    Allowance, Config, ContractStatus, Funding, RewardPool, RewardSchedule, Role, TokenRewards,
+    Unbonding, UserInfo,
 };
-use crate::state::{Config, RewardPool, UserInfo};
 use crate::viewing_key::{ViewingKey, VIEWING_KEY_SIZE};
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
@@ -21,6 +30,49 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     env: Env,
     msg: InitMsg,
 ) -> StdResult<InitResponse> {
+    if msg.inc_token_scale.u128() == 0 || msg.reward_scale.u128() == 0 {
+        return Err(ContractError::InvalidConfig {
+            reason: "scales must be greater than zero".to_string(),
+        }
+        .into());
+    }
+    if msg.max_boost < BOOST_SCALE {
+        return Err(ContractError::InvalidConfig {
+            reason: format!("max boost can't be lower than {}", BOOST_SCALE),
+        }
+        .into());
+    }
+    if msg.reward_tokens.is_empty() {
+        return Err(ContractError::InvalidConfig {
+            reason: "at least one reward token is required".to_string(),
+        }
+        .into());
+    }
+    for (i, token) in msg.reward_tokens.iter().enumerate() {
+        if msg.reward_tokens[..i]
+            .iter()
+            .any(|other| other.address == token.address)
+        {
+            return Err(ContractError::InvalidConfig {
+                reason: format!("reward token was given more than once: {}", token.address),
+            }
+            .into());
+        }
+    }
+    if msg.deadline <= msg.start_block {
+        return Err(ContractError::InvalidSchedule {
+            start: msg.start_block,
+            end: msg.deadline,
+        }
+        .into());
+    }
+
+    if let Some(funders) = &msg.allowed_funders {
+        for funder in funders {
+            deps.api.canonical_address(funder)?;
+        }
+    }
+
     // Initialize state
     let prng_seed_hashed = sha_256(&msg.prng_seed.0);
     let mut config_store = TypedStoreMut::attach(&mut deps.storage);
@@ -28,57 +80,76 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         CONFIG_KEY,
         &Config {
             admin: env.message.sender.clone(),
-            reward_token: msg.reward_token.clone(),
+            pending_admin: None,
+            contract_address: env.contract.address.clone(),
+            reward_tokens: msg.reward_tokens.clone(),
             inc_token: msg.inc_token.clone(),
+            inc_token_scale: msg.inc_token_scale.u128(),
+            reward_scale: msg.reward_scale.u128(),
+            max_lock_blocks: msg.max_lock_blocks,
+            max_boost: msg.max_boost,
+            unbonding_period: msg.unbonding_period,
+            start_block: msg.start_block,
             pool_claim_block: msg.pool_claim_block,
-            deadline: msg.deadline,
             viewing_key: msg.viewing_key.clone(),
             prng_seed: prng_seed_hashed.to_vec(),
-            is_stopped: false,
+            status: ContractStatus::Normal,
+            allowed_funders: msg.allowed_funders.clone(),
         },
     )?;
 
     TypedStoreMut::<RewardPool, S>::attach(&mut deps.storage).store(
         REWARD_POOL_KEY,
         &RewardPool {
-            pending_rewards: 0,
             inc_token_supply: 0,
+            inc_token_locked: 0,
+            inc_token_unbonding: 0,
             last_reward_block: 0,
-            acc_reward_per_share: 0,
+            rewards: vec![
+                TokenRewards {
+                    pending_rewards: 0,
+                    acc_reward_per_share: 0,
+                    dust: 0,
+                    unclaimed: 0,
+                };
+                msg.reward_tokens.len()
+            ],
+            schedules: vec![RewardSchedule {
+                start: msg.start_block,
+                end: msg.deadline,
+                amounts: vec![0; msg.reward_tokens.len()],
+            }],
         },
     )?;
 
-    // Register sSCRT and incentivized token, set vks
-    let messages = vec![
-        snip20::register_receive_msg(
+    // The creator starts with every role, and can hand them out later
+    for role in Role::ALL.iter() {
+        store_role_members(&mut deps.storage, *role, &[env.message.sender.clone()])?;
+    }
+    TypedStoreMut::attach(&mut deps.storage).store(CONTRACT_VERSION_KEY, &CONTRACT_VERSION)?;
+
+    // Register reward tokens and incentivized token, set vks
+    let mut messages = vec![];
+    for token in msg
+        .reward_tokens
+        .iter()
+        .chain(std::iter::once(&msg.inc_token))
+    {
+        messages.push(snip20::register_receive_msg(
             env.contract_code_hash.clone(),
             None,
             1, // This is public data, no need to pad
-            msg.reward_token.contract_hash.clone(),
-            msg.reward_token.address.clone(),
-        )?,
-        snip20::register_receive_msg(
-            env.contract_code_hash,
-            None,
-            1,
-            msg.inc_token.contract_hash.clone(),
-            msg.inc_token.address.clone(),
-        )?,
-        snip20::set_viewing_key_msg(
+            token.contract_hash.clone(),
+            token.address.clone(),
+        )?);
+        messages.push(snip20::set_viewing_key_msg(
             msg.viewing_key.clone(),
             None,
             RESPONSE_BLOCK_SIZE, // This is private data, need to pad
-            msg.reward_token.contract_hash,
-            msg.reward_token.address,
-        )?,
-        snip20::set_viewing_key_msg(
-            msg.viewing_key,
-            None,
-            RESPONSE_BLOCK_SIZE,
-            msg.inc_token.contract_hash,
-            msg.inc_token.address,
-        )?,
-    ];
+            token.contract_hash.clone(),
+            token.address.clone(),
+        )?);
+    }
 
     Ok(InitResponse {
         messages,
@@ -86,34 +157,192 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+/// Upgrades the stored state from the version it was stored in to `CONTRACT_VERSION`
+pub fn migrate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    _msg: MigrateMsg,
+) -> StdResult<MigrateResponse> {
+    let version: u32 = TypedStore::attach(&deps.storage)
+        .load(CONTRACT_VERSION_KEY)
+        .unwrap_or(0); // NotFound is the only possible error, and the first release didn't store a version
+    if version > CONTRACT_VERSION {
+        return Err(ContractError::UnknownVersion { version }.into());
+    }
+
+    if version < 1 {
+        upgrade_from_v0(deps, &env)?;
+    }
+    TypedStoreMut::attach(&mut deps.storage).store(CONTRACT_VERSION_KEY, &CONTRACT_VERSION)?;
+
+    Ok(MigrateResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "migrate"),
+            log("from_version", version),
+            log("to_version", CONTRACT_VERSION),
+        ],
+        data: None,
+    })
+}
+
+/// Lockups are stored under every user's address and can't be iterated, so `load_user` upgrades them as they're
+/// loaded instead
+fn upgrade_from_v0<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+) -> StdResult<()> {
+    let config: v0::Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let reward_pool: v0::RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+
+    let reward_balance = snip20::balance_query(
+        &deps.querier,
+        env.contract.address.clone(),
+        config.viewing_key.clone(),
+        RESPONSE_BLOCK_SIZE,
+        config.reward_token.contract_hash.clone(),
+        config.reward_token.address.clone(),
+    )?;
+    let deadline = config.deadline;
+    let config = upgrade_config(config, env.contract.address.clone());
+    let reward_pool =
+        upgrade_reward_pool(reward_pool, &config, deadline, reward_balance.amount.u128())?;
+
+    for role in Role::ALL.iter() {
+        store_role_members(&mut deps.storage, *role, &[config.admin.clone()])?;
+    }
+    TypedStoreMut::attach(&mut deps.storage).store(CONFIG_KEY, &config)?;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)
+}
+
+/// Every handler logs an `action` attribute with the name of the message, e.g. `deposit_rewards`. Depending on
+/// the action, these attributes follow:
+/// - `deposit`: `amount`, `unlock_height`, and either the `beneficiary` when it isn't the sender, or a `reward`
+///   for every reward token when the sender's position already existed.
+/// - `deposit_rewards`: `token`, `amount`, `schedule_start` and `schedule_end`.
+/// - `redeem`: `amount`, a `reward` for every reward token, and `release_height` when tokens are unbonding.
+/// - `claim_unbonded`, `emergency_redeem`: `amount` of the incentivized token paid out.
+/// - `claim_rewards`: a `reward` for every reward token.
+/// - `transfer_position`: `amount`, the `recipient`, and a `reward` for every reward token paid to the sender.
+/// - `transfer`, `send`, `transfer_from`, `send_from`: `amount`, the `recipient`, and a `reward` for every reward
+///   token paid to the sender, or the `owner` when the sender spent an allowance.
+/// - `increase_allowance`, `decrease_allowance`: the `spender` and the new `allowance`.
+/// - `compound`: `amount` restaked, and a `reward` for every other reward token.
+/// - `set_auto_compound`: `enabled`.
+/// - `kick`: the `address` whose boost was reset.
+/// - `compound_all`: `compounded`, the amount of users that were compounded.
+/// - `claim_reward_pool`, `sweep_dust`: a `reward` for every reward token that was withdrawn.
+/// - `propose_admin`: the proposed `pending_admin`. `accept_admin`: the new `admin`.
+/// - `set_contract_status`: the new `status`.
+/// - `pay_frozen_rewards`: the `address` that was paid.
+/// - `grant_role`, `revoke_role`: the `role` and the `address` it was granted to or revoked from.
+/// - `set_deadline`: the new `deadline`. `set_start_block`: the new `start_block`.
+/// - `set_allowed_funders`: `restricted`, whether only the allowed funders may deposit rewards.
+///
+/// A `reward` attribute is formatted as `<token address>:<amount>`. Logs on Secret Network are encrypted for
+/// the sender, so they only carry what the sender may know anyway. Viewing keys, permits and other users'
+/// balances are never logged.
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
     let config: Config = TypedStoreMut::attach(&mut deps.storage).load(CONFIG_KEY)?;
-    if config.is_stopped {
-        return match msg {
-            HandleMsg::EmergencyRedeem {} => emergency_redeem(deps, env),
-            HandleMsg::ResumeContract {} => resume_contract(deps, env),
-            _ => Err(StdError::generic_err(
-                "this contract is stopped and this action is not allowed",
-            )),
-        };
-    }
 
     let response = match msg {
-        HandleMsg::Redeem { amount } => redeem(deps, env, amount),
+        // Available in every status
+        HandleMsg::CreateViewingKey { entropy, .. } => create_viewing_key(deps, env, entropy),
+        HandleMsg::SetViewingKey { key, .. } => set_viewing_key(deps, env, key),
+        HandleMsg::RevokePermit { permit_name, .. } => revoke_permit(deps, env, permit_name),
+        HandleMsg::IncreaseAllowance {
+            spender,
+            amount,
+            expiration,
+            ..
+        } => change_allowance(deps, env, spender, amount, expiration, true),
+        HandleMsg::DecreaseAllowance {
+            spender,
+            amount,
+            expiration,
+            ..
+        } => change_allowance(deps, env, spender, amount, expiration, false),
+        HandleMsg::RegisterReceive { code_hash, .. } => register_receive(deps, env, code_hash),
+        HandleMsg::SetContractStatus { status } => set_contract_status(deps, env, status),
+        HandleMsg::PayFrozenRewards { address } => pay_frozen_rewards(deps, env, address),
+        HandleMsg::ProposeAdmin { address } => propose_admin(deps, env, address),
+        HandleMsg::AcceptAdmin {} => accept_admin(deps, env),
+        HandleMsg::CancelAdminProposal {} => cancel_admin_proposal(deps, env),
+        HandleMsg::GrantRole { role, address } => grant_role(deps, env, role, address),
+        HandleMsg::RevokeRole { role, address } => revoke_role(deps, env, role, address),
+        HandleMsg::EmergencyRedeem { recipient }
+            if config.status == ContractStatus::EmergencyOnly =>
+        {
+            emergency_redeem(deps, env, recipient)
+        }
+        _ if config.status == ContractStatus::EmergencyOnly => {
+            Err(ContractError::ContractStopped {
+                status: config.status,
+            }
+            .into())
+        }
+
+        HandleMsg::Redeem { amount, recipient } => redeem(deps, env, amount, recipient),
+        HandleMsg::ClaimUnbonded { recipient } => claim_unbonded(deps, env, recipient),
+        HandleMsg::ClaimRewards { recipient } => claim_rewards(deps, env, recipient),
+        HandleMsg::TransferPosition { recipient, amount } => {
+            transfer_position(deps, env, recipient, amount)
+        }
+        HandleMsg::Transfer {
+            recipient, amount, ..
+        } => transfer_locked(deps, env, None, recipient, amount, None),
+        HandleMsg::Send {
+            recipient,
+            recipient_code_hash,
+            amount,
+            msg,
+            ..
+        } => transfer_locked(
+            deps,
+            env,
+            None,
+            recipient,
+            amount,
+            Some((recipient_code_hash, msg)),
+        ),
+        HandleMsg::TransferFrom {
+            owner,
+            recipient,
+            amount,
+            ..
+        } => transfer_locked(deps, env, Some(owner), recipient, amount, None),
+        HandleMsg::SendFrom {
+            owner,
+            recipient,
+            recipient_code_hash,
+            amount,
+            msg,
+            ..
+        } => transfer_locked(
+            deps,
+            env,
+            Some(owner),
+            recipient,
+            amount,
+            Some((recipient_code_hash, msg)),
+        ),
+        HandleMsg::Compound {} => compound(deps, env),
+        HandleMsg::SetAutoCompound { enabled } => set_auto_compound(deps, env, enabled),
+        HandleMsg::CompoundAll { start, limit } => compound_all(deps, env, start, limit),
+        HandleMsg::Kick { address } => kick(deps, env, address),
         HandleMsg::Receive {
             from, amount, msg, ..
         } => receive(deps, env, from, amount.u128(), msg),
-        HandleMsg::CreateViewingKey { entropy, .. } => create_viewing_key(deps, env, entropy),
-        HandleMsg::SetViewingKey { key, .. } => set_viewing_key(deps, env, key),
         HandleMsg::ClaimRewardPool { to: recipient } => claim_reward_pool(deps, env, recipient),
-        HandleMsg::StopContract {} => stop_contract(deps, env),
-        HandleMsg::ChangeAdmin { address } => change_admin(deps, env, address),
         HandleMsg::SetDeadline { block: height } => set_deadline(deps, env, height),
-        _ => Err(StdError::generic_err("Unavailable or unknown action")),
+        HandleMsg::SetStartBlock { block: height } => set_start_block(deps, env, height),
+        HandleMsg::SetAllowedFunders { funders } => set_allowed_funders(deps, env, funders),
+        HandleMsg::SweepDust { to: recipient } => sweep_dust(deps, env, recipient),
+        _ => Err(ContractError::UnknownAction {}.into()),
     };
 
     pad_handle_result(response, RESPONSE_BLOCK_SIZE)
@@ -126,11 +355,24 @@ pub fn query<S: Storage, A: Api, Q: Querier>(
     let response = match msg {
         QueryMsg::ClaimBlock {} => query_claim_block(deps),
         QueryMsg::ContractStatus {} => query_contract_status(deps),
-        QueryMsg::RewardToken {} => query_reward_token(deps),
+        QueryMsg::PendingAdmin {} => query_pending_admin(deps),
+        QueryMsg::Roles {} => query_roles(deps),
+        QueryMsg::RewardTokens {} => query_reward_tokens(deps),
         QueryMsg::IncentivizedToken {} => query_incentivized_token(deps),
+        QueryMsg::StartHeight {} => query_start_height(deps),
         QueryMsg::EndHeight {} => query_end_height(deps),
         QueryMsg::RewardPoolBalance {} => query_reward_pool_balance(deps),
-        QueryMsg::TokenInfo {} => query_token_info(),
+        QueryMsg::RewardDust {} => query_reward_dust(deps),
+        QueryMsg::ActiveSchedules { height } => query_schedules(deps, |schedule| {
+            schedule.start <= height && height < schedule.end
+        }),
+        QueryMsg::UpcomingSchedules { height } => {
+            query_schedules(deps, |schedule| height < schedule.start)
+        }
+        QueryMsg::AllowedFunders {} => query_allowed_funders(deps),
+        QueryMsg::FundingHistory { start, limit } => query_funding_history(deps, start, limit),
+        QueryMsg::TokenInfo {} => query_token_info(deps),
+        QueryMsg::WithPermit { permit, query } => permit_queries(deps, permit, query),
         _ => authenticated_queries(deps, msg),
     };
 
@@ -141,23 +383,28 @@ pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     msg: QueryMsg,
 ) -> StdResult<Binary> {
-    let (address, key) = msg.get_validation_params();
+    let (addresses, key) = msg.get_validation_params();
 
     let vk_store = ReadonlyPrefixedStorage::new(VIEWING_KEY_KEY, &deps.storage);
-    let expected_key = vk_store.get(address.0.as_bytes());
-
-    if expected_key.is_none() {
-        // Checking the key will take significant time. We don't want to exit immediately if it isn't set
-        // in a way which will allow to time the command and determine if a viewing key doesn't exist
-        key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
-    } else if key.check_viewing_key(expected_key.unwrap().as_slice()) {
-        return match msg {
-            QueryMsg::Rewards {
-                address, height, ..
-            } => query_pending_rewards(deps, &address, height),
-            QueryMsg::Deposit { address, .. } => query_deposit(deps, &address),
-            _ => panic!("This should never happen"),
-        };
+    for address in addresses {
+        let expected_key = vk_store.get(address.0.as_bytes());
+
+        if expected_key.is_none() {
+            // Checking the key will take significant time. We don't want to exit immediately if it isn't set
+            // in a way which will allow to time the command and determine if a viewing key doesn't exist
+            key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
+        } else if key.check_viewing_key(expected_key.unwrap().as_slice()) {
+            return match &msg {
+                QueryMsg::Balance { address, .. } => query_balance(deps, address),
+                QueryMsg::Allowance { owner, spender, .. } => query_allowance(deps, owner, spender),
+                QueryMsg::Rewards {
+                    address, height, ..
+                } => query_pending_rewards(deps, address, *height),
+                QueryMsg::Deposit { address, .. } => query_deposit(deps, address),
+                QueryMsg::Unbondings { address, .. } => query_unbondings(deps, address),
+                _ => panic!("This should never happen"),
+            };
+        }
     }
 
     Ok(to_binary(&QueryAnswer::QueryError {
@@ -165,6 +412,43 @@ pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
     })?)
 }
 
+pub fn permit_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: Permit,
+    query: QueryWithPermit,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let address = permit.validate(&deps.storage, &deps.api, &config.contract_address)?;
+
+    let permission = match query {
+        QueryWithPermit::Balance {} => Permission::Balance,
+        QueryWithPermit::Allowance { .. } => Permission::Allowance,
+        QueryWithPermit::Rewards { .. } => Permission::Rewards,
+        QueryWithPermit::Deposit {} => Permission::Deposit,
+        QueryWithPermit::Unbondings {} => Permission::Unbondings,
+    };
+    if !permit.check_permission(&permission) {
+        return Err(ContractError::MissingPermission {
+            permit_name: permit.params.permit_name,
+            permission: format!("{:?}", permission),
+        }
+        .into());
+    }
+
+    match query {
+        QueryWithPermit::Balance {} => query_balance(deps, &address),
+        QueryWithPermit::Allowance { owner, spender } => {
+            if address != owner && address != spender {
+                return Err(ContractError::NotAllowanceParty { address }.into());
+            }
+            query_allowance(deps, &owner, &spender)
+        }
+        QueryWithPermit::Rewards { height } => query_pending_rewards(deps, &address, height),
+        QueryWithPermit::Deposit {} => query_deposit(deps, &address),
+        QueryWithPermit::Unbondings {} => query_unbondings(deps, &address),
+    }
+}
+
 // Handle functions
 
 fn receive<S: Storage, A: Api, Q: Querier>(
@@ -177,544 +461,2653 @@ fn receive<S: Storage, A: Api, Q: Querier>(
     let msg: ReceiveMsg = from_binary(&msg)?;
 
     match msg {
-        ReceiveMsg::Deposit {} => deposit(deps, env, from, amount),
-        ReceiveMsg::DepositRewards {} => deposit_rewards(deps, env, amount),
+        ReceiveMsg::Deposit {
+            lock_blocks,
+            beneficiary,
+        } => {
+            let beneficiary = beneficiary.unwrap_or_else(|| from.clone());
+            deps.api.canonical_address(&beneficiary)?;
+            deposit(
+                deps,
+                env,
+                from,
+                beneficiary,
+                amount,
+                lock_blocks.unwrap_or(0),
+            )
+        }
+        ReceiveMsg::DepositRewards { start, end } => {
+            deposit_rewards(deps, env, from, amount, start, end)
+        }
     }
 }
 
+/// Tokens can be locked for another address, which then owns the position and gets its pending rewards. Only
+/// the owner can push the unlock height of tokens it already has locked
 fn deposit<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     from: HumanAddr,
+    beneficiary: HumanAddr,
     amount: u128,
+    lock_blocks: u64,
 ) -> StdResult<HandleResponse> {
     // Ensure that the sent tokens are from an expected contract address
     let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
     if env.message.sender != config.inc_token.address {
-        return Err(StdError::generic_err(format!(
-            "This token is not supported. Supported: {}, given: {}",
-            config.inc_token.address, env.message.sender
-        )));
+        return Err(ContractError::UnsupportedToken {
+            supported: config.inc_token.address.to_string(),
+            given: env.message.sender,
+        }
+        .into());
     }
 
-    // Adjust scale to allow easy division and prevent overflows
-    let amount = amount / INC_TOKEN_SCALE;
+    enforce_deposits(&config)?;
+
+    if lock_blocks > config.max_lock_blocks {
+        return Err(ContractError::LockupTooLong {
+            max: config.max_lock_blocks,
+            given: lock_blocks,
+        }
+        .into());
+    }
+    // Otherwise anyone could lock an address out of its own later deposits
+    if from != beneficiary && lock_blocks > 0 {
+        return Err(ContractError::LockupExtensionNotAllowed {
+            address: beneficiary,
+        }
+        .into());
+    }
 
     let mut reward_pool = update_rewards(deps, &env, &config)?;
 
     let mut messages: Vec<CosmosMsg> = vec![];
-    let mut users_store = TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage);
-    let mut user = users_store
-        .load(from.0.as_bytes())
-        .unwrap_or(UserInfo { locked: 0, debt: 0 }); // NotFound is the only possible error
-
-    if user.locked > 0 {
-        let pending = user.locked * reward_pool.acc_reward_per_share / REWARD_SCALE - user.debt;
-        if pending > 0 {
-            messages.push(secret_toolkit::snip20::transfer_msg(
-                from.clone(),
-                Uint128(pending),
-                None,
-                RESPONSE_BLOCK_SIZE,
-                config.reward_token.contract_hash,
-                config.reward_token.address,
-            )?);
+    let mut user = load_user(&deps.storage, &beneficiary, &config)?;
+
+    // The whole position is locked until the latest unlock height. Tokens that were already locked keep
+    // their boost, while the new tokens are boosted by the blocks left until unlock
+    let height = env.block.height;
+    let unlock_height = height
+        .checked_add(lock_blocks)
+        .ok_or(ContractError::Overflow {})?;
+
+    let mut logs = vec![log("action", "deposit"), log("amount", amount)];
+    if from != beneficiary {
+        logs.push(log("beneficiary", &beneficiary));
+    }
+    if user.locked > 0 || user.frozen_rewards.iter().any(|amount| *amount > 0) {
+        let pending = pending_rewards(&user, &reward_pool, &config)?;
+        settle_unclaimed(&mut reward_pool, &config, &pending)?;
+        messages.extend(reward_transfer_msgs(&config, &beneficiary, &pending)?);
+        // Logs are readable by the sender, so a funder doesn't learn the beneficiary's rewards
+        if from == beneficiary {
+            logs.extend(reward_logs(&config, &pending));
         }
     }
 
-    user.locked += amount;
-    user.debt = user.locked * reward_pool.acc_reward_per_share / REWARD_SCALE;
-    users_store.store(from.0.as_bytes(), &user)?;
+    if user.unlock_height <= height {
+        user.boost = BOOST_SCALE;
+    }
+    user.unlock_height = std::cmp::max(user.unlock_height, unlock_height);
+    let deposit_boost = lock_boost(&config, user.unlock_height - height);
+    add_locked(&mut user, amount, deposit_boost)?;
+
+    reward_pool.inc_token_locked = reward_pool
+        .inc_token_locked
+        .checked_add(amount)
+        .ok_or(ContractError::Overflow {})?;
+    update_shares(&mut user, &mut reward_pool, &config)?;
+    reset_debt(&mut user, &reward_pool, &config)?;
+    TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage)
+        .store(beneficiary.0.as_bytes(), &user)?;
 
-    reward_pool.inc_token_supply += amount;
     TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
 
+    logs.push(log("unlock_height", user.unlock_height));
+
     Ok(HandleResponse {
         messages,
-        log: vec![],
+        log: logs,
         data: Some(to_binary(&ReceiveAnswer::Deposit { status: Success })?),
     })
 }
 
+/// Adds rewards to a new schedule from `start` (or now) to `end`, or to an existing schedule with the same
+/// blocks. Without `end` the rewards top up the schedule that ends last. The token's dust is added as well
 fn deposit_rewards<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
+    from: HumanAddr,
     amount: u128,
+    start: Option<u64>,
+    end: Option<u64>,
 ) -> StdResult<HandleResponse> {
     let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
-    if env.message.sender != config.reward_token.address {
-        return Err(StdError::generic_err(format!(
-            "This token is not supported. Supported: {}, given: {}",
-            config.reward_token.address, env.message.sender
-        )));
+    let token_idx = config
+        .reward_tokens
+        .iter()
+        .position(|token| token.address == env.message.sender)
+        .ok_or_else(|| ContractError::UnsupportedToken {
+            supported: config
+                .reward_tokens
+                .iter()
+                .map(|token| token.address.0.as_str())
+                .collect::<Vec<&str>>()
+                .join(", "),
+            given: env.message.sender.clone(),
+        })?;
+    if let Some(funders) = &config.allowed_funders {
+        if !funders.contains(&from) {
+            return Err(ContractError::FunderNotAllowed { address: from }.into());
+        }
+    }
+    if amount == 0 {
+        return Err(ContractError::ZeroRewards {}.into());
+    }
+    // Anyone else can only top up the current schedule
+    if start.is_some() || end.is_some() {
+        enforce_role(&deps.storage, Role::CampaignManager, &from)?;
     }
 
+    push_funding(
+        &mut deps.storage,
+        &Funding {
+            funder: from,
+            token: env.message.sender.clone(),
+            amount,
+            block: env.block.height,
+        },
+    )?;
     let mut reward_pool = update_rewards(deps, &env, &config)?;
 
-    reward_pool.pending_rewards += amount - 1_000_000; // Subtracting 1scrt just to give room for rounding errors in calculations
+    let schedule_idx = match end {
+        Some(end) => {
+            let start = *[start.unwrap_or(0), env.block.height, config.start_block]
+                .iter()
+                .max()
+                .unwrap();
+            if end <= start {
+                return Err(ContractError::InvalidSchedule { start, end }.into());
+            }
+            add_schedule(&mut reward_pool, config.reward_tokens.len(), start, end)?
+        }
+        None if start.is_some() => {
+            return Err(ContractError::MissingScheduleEnd {}.into());
+        }
+        None => last_schedule_idx(&reward_pool),
+    };
+    let token_rewards = &mut reward_pool.rewards[token_idx];
+    let scheduled = amount
+        .checked_add(take_dust(token_rewards, &config))
+        .ok_or(ContractError::Overflow {})?;
+    token_rewards.pending_rewards = token_rewards
+        .pending_rewards
+        .checked_add(scheduled)
+        .ok_or(ContractError::Overflow {})?;
+    let schedule_amount = &mut reward_pool.schedules[schedule_idx].amounts[token_idx];
+    *schedule_amount = schedule_amount
+        .checked_add(scheduled)
+        .ok_or(ContractError::Overflow {})?;
+    let schedule = &reward_pool.schedules[schedule_idx];
+    let logs = vec![
+        log("action", "deposit_rewards"),
+        log("token", &env.message.sender),
+        log("amount", amount),
+        log("schedule_start", schedule.start),
+        log("schedule_end", schedule.end),
+    ];
     TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
 
     Ok(HandleResponse {
         messages: vec![],
-        log: vec![],
+        log: logs,
         data: Some(to_binary(&ReceiveAnswer::DepositRewards {
             status: Success,
         })?),
     })
 }
 
+/// With an unbonding period only the rewards go to `recipient` right away. The redeemed tokens are paid by
+/// `claim_unbonded`, to the recipient given there
 fn redeem<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     amount: Option<Uint128>,
+    recipient: Option<HumanAddr>,
 ) -> StdResult<HandleResponse> {
     let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
-    let mut user = TypedStore::<UserInfo, S>::attach(&deps.storage)
-        .load(env.message.sender.0.as_bytes())
-        .unwrap_or(UserInfo { locked: 0, debt: 0 }); // NotFound is the only possible error
-    let amount = amount
-        .unwrap_or(Uint128(user.locked * INC_TOKEN_SCALE)) // Multiplying to match scale of input, dividing again later
-        .u128()
-        / INC_TOKEN_SCALE;
+    let recipient = recipient.unwrap_or_else(|| env.message.sender.clone());
+    deps.api.canonical_address(&recipient)?;
+    let mut user = load_user(&deps.storage, &env.message.sender, &config)?;
+    let amount = amount.unwrap_or(Uint128(user.locked)).u128();
 
     if amount > user.locked {
-        return Err(StdError::generic_err(format!(
-            "insufficient funds to redeem: balance={}, required={}",
-            user.locked * INC_TOKEN_SCALE,
-            amount * INC_TOKEN_SCALE,
-        )));
+        return Err(ContractError::InsufficientFunds {
+            balance: user.locked,
+            required: amount,
+        }
+        .into());
     }
 
-    let mut messages: Vec<CosmosMsg> = vec![];
-    let mut reward_pool = update_rewards(deps, &env, &config)?;
-    let pending = user.locked * reward_pool.acc_reward_per_share / REWARD_SCALE - user.debt;
-    if pending > 0 {
-        // Transfer rewards
-        messages.push(secret_toolkit::snip20::transfer_msg(
-            env.message.sender.clone(),
-            Uint128(pending),
-            None,
-            RESPONSE_BLOCK_SIZE,
-            config.reward_token.contract_hash,
-            config.reward_token.address,
-        )?);
+    if amount > 0 && env.block.height < user.unlock_height {
+        return Err(ContractError::TokensLocked {
+            unlock_height: user.unlock_height,
+        }
+        .into());
     }
 
+    let mut reward_pool = update_rewards(deps, &env, &config)?;
+
+    // Transfer rewards
+    let pending = pending_rewards(&user, &reward_pool, &config)?;
+    settle_unclaimed(&mut reward_pool, &config, &pending)?;
+    let mut messages = reward_transfer_msgs(&config, &recipient, &pending)?;
+    let mut logs = vec![log("action", "redeem"), log("amount", amount)];
+    logs.extend(reward_logs(&config, &pending));
+
     // Transfer redeemed tokens
+    if user.unlock_height <= env.block.height {
+        user.boost = BOOST_SCALE;
+    }
     user.locked -= amount;
-    user.debt = user.locked * reward_pool.acc_reward_per_share / REWARD_SCALE;
+    reward_pool.inc_token_locked = reward_pool
+        .inc_token_locked
+        .checked_sub(amount)
+        .ok_or(ContractError::Underflow {})?;
+    if config.unbonding_period != 0 {
+        reward_pool.inc_token_unbonding = reward_pool
+            .inc_token_unbonding
+            .checked_add(amount)
+            .ok_or(ContractError::Overflow {})?;
+    }
+    update_shares(&mut user, &mut reward_pool, &config)?;
+    reset_debt(&mut user, &reward_pool, &config)?;
     TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage)
         .store(env.message.sender.0.as_bytes(), &user)?;
 
-    reward_pool.inc_token_supply -= amount;
     TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
 
-    messages.push(secret_toolkit::snip20::transfer_msg(
-        env.message.sender,
-        Uint128(amount * INC_TOKEN_SCALE),
-        None,
-        RESPONSE_BLOCK_SIZE,
-        config.inc_token.contract_hash,
-        config.inc_token.address,
-    )?);
+    if amount > 0 {
+        if config.unbonding_period == 0 {
+            messages.push(secret_toolkit::snip20::transfer_msg(
+                recipient,
+                Uint128(amount),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                config.inc_token.contract_hash,
+                config.inc_token.address,
+            )?);
+        } else {
+            // Redeemed tokens stop earning rewards right away, but can only be claimed after unbonding
+            let release_height = env
+                .block
+                .height
+                .checked_add(config.unbonding_period)
+                .ok_or(ContractError::Overflow {})?;
+            let mut unbondings = load_unbondings(&deps.storage, &env.message.sender);
+            unbondings.push(Unbonding {
+                amount,
+                release_height,
+            });
+            store_unbondings(&mut deps.storage, &env.message.sender, unbondings)?;
+            logs.push(log("release_height", release_height));
+        }
+    }
 
     Ok(HandleResponse {
         messages,
-        log: vec![],
+        log: logs,
         data: Some(to_binary(&HandleAnswer::Redeem { status: Success })?),
     })
 }
 
-pub fn create_viewing_key<S: Storage, A: Api, Q: Querier>(
+fn claim_unbonded<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    entropy: String,
+    recipient: Option<HumanAddr>,
 ) -> StdResult<HandleResponse> {
-    let config: Config = TypedStoreMut::attach(&mut deps.storage).load(CONFIG_KEY)?;
-    let prng_seed = config.prng_seed;
-
-    let key = ViewingKey::new(&env, &prng_seed, (&entropy).as_ref());
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    let recipient = recipient.unwrap_or_else(|| env.message.sender.clone());
+    deps.api.canonical_address(&recipient)?;
+
+    let (matured, unbonding): (Vec<Unbonding>, Vec<Unbonding>) =
+        load_unbondings(&deps.storage, &env.message.sender)
+            .into_iter()
+            .partition(|unbonding| unbonding.release_height <= env.block.height);
+    store_unbondings(&mut deps.storage, &env.message.sender, unbonding)?;
+
+    let amount = total_unbonding(&matured)?;
+    let mut reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+    reward_pool.inc_token_unbonding = reward_pool
+        .inc_token_unbonding
+        .checked_sub(amount)
+        .ok_or(ContractError::Underflow {})?;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
 
-    let mut vk_store = PrefixedStorage::new(VIEWING_KEY_KEY, &mut deps.storage);
-    vk_store.set(env.message.sender.0.as_bytes(), &key.to_hashed());
+    let mut messages = vec![];
+    if amount > 0 {
+        messages.push(secret_toolkit::snip20::transfer_msg(
+            recipient,
+            Uint128(amount),
+            None,
+            RESPONSE_BLOCK_SIZE,
+            config.inc_token.contract_hash,
+            config.inc_token.address,
+        )?);
+    }
 
     Ok(HandleResponse {
-        messages: vec![],
-        log: vec![],
-        data: Some(to_binary(&HandleAnswer::CreateViewingKey { key })?),
+        messages,
+        log: vec![log("action", "claim_unbonded"), log("amount", amount)],
+        data: Some(to_binary(&HandleAnswer::ClaimUnbonded { status: Success })?),
     })
 }
 
-pub fn set_viewing_key<S: Storage, A: Api, Q: Querier>(
+fn transfer_position<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    key: String,
+    recipient: HumanAddr,
+    amount: Option<Uint128>,
 ) -> StdResult<HandleResponse> {
-    let vk = ViewingKey(key);
-
-    let mut vk_store = PrefixedStorage::new(VIEWING_KEY_KEY, &mut deps.storage);
-    vk_store.set(env.message.sender.0.as_bytes(), &vk.to_hashed());
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    let owner = env.message.sender.clone();
+    let (amount, messages, owner_rewards) =
+        move_position(deps, &env, &config, &owner, &recipient, amount)?;
+
+    let mut logs = vec![
+        log("action", "transfer_position"),
+        log("amount", amount),
+        log("recipient", &recipient),
+    ];
+    logs.extend(reward_logs(&config, &owner_rewards));
 
     Ok(HandleResponse {
-        messages: vec![],
-        log: vec![],
-        data: Some(to_binary(&HandleAnswer::SetViewingKey { status: Success })?),
+        messages,
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::TransferPosition {
+            status: Success,
+        })?),
     })
 }
 
-fn claim_reward_pool<S: Storage, A: Api, Q: Querier>(
+/// Transfers the sender's locked tokens like a SNIP-20 token, or `owner`'s by spending the allowance it gave the
+/// sender. With `send`, a recipient that registered its code hash, or whose code hash is given, is notified with
+/// a `Receive` callback carrying the message
+fn transfer_locked<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    recipient: Option<HumanAddr>,
+    owner: Option<HumanAddr>,
+    recipient: HumanAddr,
+    amount: Uint128,
+    send: Option<(Option<String>, Option<Binary>)>,
 ) -> StdResult<HandleResponse> {
-    let config_store = TypedStore::attach(&deps.storage);
-    let config: Config = config_store.load(CONFIG_KEY)?;
-
-    enforce_admin(config.clone(), env.clone())?;
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    let spender = env.message.sender.clone();
+    let (action, answer) = match (&owner, &send) {
+        (None, None) => ("transfer", HandleAnswer::Transfer { status: Success }),
+        (None, Some(_)) => ("send", HandleAnswer::Send { status: Success }),
+        (Some(_), None) => (
+            "transfer_from",
+            HandleAnswer::TransferFrom { status: Success },
+        ),
+        (Some(_), Some(_)) => ("send_from", HandleAnswer::SendFrom { status: Success }),
+    };
+    let owner = owner.unwrap_or_else(|| spender.clone());
+    if owner != spender {
+        spend_allowance(&mut deps.storage, &env, &owner, &spender, amount.u128())?;
+    }
 
-    if env.block.height < config.pool_claim_block {
-        return Err(StdError::generic_err(format!(
-            "minimum claim height hasn't passed yet: {}",
-            config.pool_claim_block
-        )));
+    let (amount, mut messages, owner_rewards) =
+        move_position(deps, &env, &config, &owner, &recipient, Some(amount))?;
+    if let Some((recipient_code_hash, msg)) = send {
+        if let Some(code_hash) =
+            recipient_code_hash.or_else(|| load_receiver(&deps.storage, &recipient))
+        {
+            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: recipient.clone(),
+                callback_code_hash: code_hash,
+                msg: to_binary(&ReceiverHandleMsg::Receive {
+                    sender: spender.clone(),
+                    from: owner.clone(),
+                    amount: Uint128(amount),
+                    msg,
+                })?,
+                send: vec![],
+            }));
+        }
     }
 
-    let total_rewards = snip20::balance_query(
-        &deps.querier,
-        env.contract.address,
-        config.viewing_key,
-        RESPONSE_BLOCK_SIZE,
-        env.contract_code_hash,
-        config.reward_token.address.clone(),
-    )?;
+    let mut logs = vec![
+        log("action", action),
+        log("amount", amount),
+        log("recipient", &recipient),
+    ];
+    // Logs are readable by the sender, so a spender doesn't learn the owner's rewards
+    if owner == spender {
+        logs.extend(reward_logs(&config, &owner_rewards));
+    } else {
+        logs.push(log("owner", &owner));
+    }
 
     Ok(HandleResponse {
-        messages: vec![snip20::transfer_msg(
-            recipient.unwrap_or(env.message.sender),
-            total_rewards.amount,
-            None,
-            RESPONSE_BLOCK_SIZE,
-            config.reward_token.contract_hash,
-            config.reward_token.address,
-        )?],
-        log: vec![],
-        data: Some(to_binary(&HandleAnswer::ClaimRewardPool {
-            status: Success,
-        })?),
+        messages,
+        log: logs,
+        data: Some(to_binary(&answer)?),
     })
 }
 
-fn stop_contract<S: Storage, A: Api, Q: Querier>(
+/// Moves `amount` of `owner`'s locked tokens, or all of them, to `recipient`, and returns the amount moved, the
+/// messages paying both users' pending rewards, and the rewards paid to `owner`.
+///
+/// The shares move along with the tokens, so the boost they earned and the pool's supply don't change, and the
+/// tokens stay locked until the owner's unlock height. Like deposits for a beneficiary, this can't push the
+/// unlock height of tokens the recipient already has locked
+fn move_position<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: Env,
-) -> StdResult<HandleResponse> {
-    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
-    let mut config: Config = config_store.load(CONFIG_KEY)?;
+    env: &Env,
+    config: &Config,
+    owner: &HumanAddr,
+    recipient: &HumanAddr,
+    amount: Option<Uint128>,
+) -> StdResult<(u128, Vec<CosmosMsg>, Vec<u128>)> {
+    deps.api.canonical_address(recipient)?;
+    if recipient == owner {
+        return Err(ContractError::TransferToSelf {}.into());
+    }
 
-    enforce_admin(config.clone(), env)?;
+    let mut sender = load_user(&deps.storage, owner, config)?;
+    let mut receiver = load_user(&deps.storage, recipient, config)?;
+    let amount = amount.unwrap_or(Uint128(sender.locked)).u128();
+    if amount > sender.locked {
+        return Err(ContractError::InsufficientFunds {
+            balance: sender.locked,
+            required: amount,
+        }
+        .into());
+    }
 
-    config.is_stopped = true;
-    config_store.store(CONFIG_KEY, &config)?;
+    // The recipient didn't agree to a lockup, so tokens can only move once they're unlocked
+    if sender.unlock_height > env.block.height {
+        return Err(ContractError::TokensLocked {
+            unlock_height: sender.unlock_height,
+        }
+        .into());
+    }
+
+    let mut reward_pool = update_rewards(deps, env, config)?;
+    let sender_pending = pending_rewards(&sender, &reward_pool, config)?;
+    settle_unclaimed(&mut reward_pool, config, &sender_pending)?;
+    let receiver_pending = pending_rewards(&receiver, &reward_pool, config)?;
+    settle_unclaimed(&mut reward_pool, config, &receiver_pending)?;
 
+    // The lockup is over, so its boost doesn't move along with the tokens
+    if sender.boost != BOOST_SCALE {
+        sender.boost = BOOST_SCALE;
+        update_shares(&mut sender, &mut reward_pool, config)?;
+    }
+
+    let shares = if amount == sender.locked {
+        sender.shares
+    } else {
+        sender
+            .shares
+            .checked_mul(amount)
+            .ok_or(ContractError::Overflow {})?
+            / sender.locked
+    };
+    add_locked(&mut receiver, amount, sender.boost)?;
+    receiver.shares = receiver
+        .shares
+        .checked_add(shares)
+        .ok_or(ContractError::Overflow {})?;
+    sender.locked -= amount;
+    sender.shares -= shares;
+
+    reset_debt(&mut sender, &reward_pool, config)?;
+    reset_debt(&mut receiver, &reward_pool, config)?;
+    TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage).store(owner.0.as_bytes(), &sender)?;
+    TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage)
+        .store(recipient.0.as_bytes(), &receiver)?;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+
+    let mut messages = reward_transfer_msgs(config, owner, &sender_pending)?;
+    messages.extend(reward_transfer_msgs(config, recipient, &receiver_pending)?);
+
+    Ok((amount, messages, sender_pending))
+}
+
+fn change_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    spender: HumanAddr,
+    amount: Uint128,
+    expiration: Option<u64>,
+    increase: bool,
+) -> StdResult<HandleResponse> {
+    deps.api.canonical_address(&spender)?;
+    let owner = env.message.sender;
+
+    let mut allowance = load_allowance(&deps.storage, &owner, &spender);
+    allowance.amount = if increase {
+        allowance.amount.saturating_add(amount.u128())
+    } else {
+        allowance.amount.saturating_sub(amount.u128())
+    };
+    if expiration.is_some() {
+        allowance.expiration = expiration;
+    }
+    store_allowance(&mut deps.storage, &owner, &spender, &allowance)?;
+
+    let (action, answer) = if increase {
+        (
+            "increase_allowance",
+            HandleAnswer::IncreaseAllowance {
+                allowance: Uint128(allowance.amount),
+            },
+        )
+    } else {
+        (
+            "decrease_allowance",
+            HandleAnswer::DecreaseAllowance {
+                allowance: Uint128(allowance.amount),
+            },
+        )
+    };
     Ok(HandleResponse {
         messages: vec![],
-        log: vec![],
-        data: Some(to_binary(&HandleAnswer::StopContract { status: Success })?),
+        log: vec![
+            log("action", action),
+            log("spender", &spender),
+            log("allowance", allowance.amount),
+        ],
+        data: Some(to_binary(&answer)?),
     })
 }
 
-fn resume_contract<S: Storage, A: Api, Q: Querier>(
+fn register_receive<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
+    code_hash: String,
 ) -> StdResult<HandleResponse> {
-    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
-    let mut config: Config = config_store.load(CONFIG_KEY)?;
-
-    enforce_admin(config.clone(), env)?;
-
-    config.is_stopped = false;
-    config_store.store(CONFIG_KEY, &config)?;
+    let mut receivers_store = PrefixedStorage::new(RECEIVERS_KEY, &mut deps.storage);
+    TypedStoreMut::<String, _>::attach(&mut receivers_store)
+        .store(env.message.sender.0.as_bytes(), &code_hash)?;
 
     Ok(HandleResponse {
         messages: vec![],
-        log: vec![],
-        data: Some(to_binary(&HandleAnswer::ResumeContract {
+        log: vec![log("action", "register_receive")],
+        data: Some(to_binary(&HandleAnswer::RegisterReceive {
             status: Success,
         })?),
     })
 }
 
-fn change_admin<S: Storage, A: Api, Q: Querier>(
+fn claim_rewards<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    address: HumanAddr,
+    recipient: Option<HumanAddr>,
 ) -> StdResult<HandleResponse> {
-    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
-    let mut config: Config = config_store.load(CONFIG_KEY)?;
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    let recipient = recipient.unwrap_or_else(|| env.message.sender.clone());
+    deps.api.canonical_address(&recipient)?;
 
-    enforce_admin(config.clone(), env)?;
+    let mut user = load_user(&deps.storage, &env.message.sender, &config)?;
 
-    config.admin = address;
-    config_store.store(CONFIG_KEY, &config)?;
+    let mut reward_pool = update_rewards(deps, &env, &config)?;
+    let pending = pending_rewards(&user, &reward_pool, &config)?;
+    settle_unclaimed(&mut reward_pool, &config, &pending)?;
+
+    reset_debt(&mut user, &reward_pool, &config)?;
+    TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage)
+        .store(env.message.sender.0.as_bytes(), &user)?;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+
+    let mut logs = vec![log("action", "claim_rewards")];
+    logs.extend(reward_logs(&config, &pending));
 
     Ok(HandleResponse {
-        messages: vec![],
-        log: vec![],
-        data: Some(to_binary(&HandleAnswer::ChangeAdmin { status: Success })?),
+        messages: reward_transfer_msgs(&config, &recipient, &pending)?,
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::ClaimRewards {
+            rewards: reward_amounts(&config, pending),
+        })?),
     })
 }
 
-/// YOU SHOULD NEVER USE THIS! This will erase any eligibility for rewards you earned so far
-fn emergency_redeem<S: Storage, A: Api, Q: Querier>(
+fn compound<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
 ) -> StdResult<HandleResponse> {
-    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
-    let mut user: UserInfo = TypedStoreMut::attach(&mut deps.storage)
-        .load(env.message.sender.0.as_bytes())
-        .unwrap_or(UserInfo { locked: 0, debt: 0 });
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    let token_idx = compounding_token_idx(&config)?;
+    enforce_deposits(&config)?;
 
-    let mut reward_pool: RewardPool =
-        TypedStoreMut::attach(&mut deps.storage).load(REWARD_POOL_KEY)?;
-    reward_pool.inc_token_supply -= user.locked;
-    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+    let mut user = load_user(&deps.storage, &env.message.sender, &config)?;
 
-    let mut messages = vec![];
-    if user.locked > 0 {
-        messages.push(secret_toolkit::snip20::transfer_msg(
-            env.message.sender.clone(),
-            Uint128(user.locked * INC_TOKEN_SCALE),
-            None,
-            RESPONSE_BLOCK_SIZE,
-            config.inc_token.contract_hash,
-            config.inc_token.address,
-        )?);
+    let mut reward_pool = update_rewards(deps, &env, &config)?;
+    let locked = user.locked;
+    let pending = compound_user(
+        &mut user,
+        &mut reward_pool,
+        &config,
+        token_idx,
+        env.block.height,
+    )?;
+    let mut logs = vec![
+        log("action", "compound"),
+        log("amount", user.locked - locked),
+    ];
+    logs.extend(reward_logs(&config, &pending));
+
+    TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage)
+        .store(env.message.sender.0.as_bytes(), &user)?;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+
+    Ok(HandleResponse {
+        messages: reward_transfer_msgs(&config, &env.message.sender, &pending)?,
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::Compound { status: Success })?),
+    })
+}
+
+fn set_auto_compound<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    enabled: bool,
+) -> StdResult<HandleResponse> {
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    compounding_token_idx(&config)?;
+
+    let mut user = load_user(&deps.storage, &env.message.sender, &config)?;
+    if enabled && user.locked == 0 {
+        return Err(ContractError::NoDeposit {
+            address: env.message.sender,
+        }
+        .into());
     }
 
-    user = UserInfo { locked: 0, debt: 0 };
-    TypedStoreMut::attach(&mut deps.storage).store(env.message.sender.0.as_bytes(), &user)?;
+    if enabled && !user.auto_compound {
+        add_compounder(&mut deps.storage, &env.message.sender)?;
+    } else if !enabled && user.auto_compound {
+        remove_compounder(&mut deps.storage, &env.message.sender)?;
+    }
+    user.auto_compound = enabled;
+    TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage)
+        .store(env.message.sender.0.as_bytes(), &user)?;
 
     Ok(HandleResponse {
-        messages,
-        log: vec![],
-        data: Some(to_binary(&HandleAnswer::EmergencyRedeem {
+        messages: vec![],
+        log: vec![log("action", "set_auto_compound"), log("enabled", enabled)],
+        data: Some(to_binary(&HandleAnswer::SetAutoCompound {
             status: Success,
         })?),
     })
 }
 
-fn set_deadline<S: Storage, A: Api, Q: Querier>(
+/// Resets the boost of a position whose lockup ended, so it stops earning boosted rewards without waiting for its
+/// owner to change it. Anyone can call this. Nothing is paid out, the rewards earned so far are kept as
+/// `UserInfo::frozen_rewards` instead
+fn kick<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    height: u64,
+    address: HumanAddr,
 ) -> StdResult<HandleResponse> {
-    let mut config = TypedStoreMut::<Config, S>::attach(&mut deps.storage).load(CONFIG_KEY)?;
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    let mut user = load_user(&deps.storage, &address, &config)?;
+    if user.boost == BOOST_SCALE || user.unlock_height > env.block.height {
+        return Err(ContractError::NothingToKick { address }.into());
+    }
 
-    enforce_admin(config.clone(), env.clone())?;
-    update_rewards(deps, &env, &config)?;
+    let mut reward_pool = update_rewards(deps, &env, &config)?;
+    let pending = pending_rewards(&user, &reward_pool, &config)?;
 
-    config.deadline = height;
-    TypedStoreMut::<Config, S>::attach(&mut deps.storage).store(CONFIG_KEY, &config)?;
+    user.boost = BOOST_SCALE;
+    update_shares(&mut user, &mut reward_pool, &config)?;
+    reset_debt(&mut user, &reward_pool, &config)?;
+    user.frozen_rewards = pending;
+    TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage).store(address.0.as_bytes(), &user)?;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
 
     Ok(HandleResponse {
         messages: vec![],
-        log: vec![],
-        data: Some(to_binary(&HandleAnswer::SetDeadline { status: Success })?),
+        log: vec![log("action", "kick"), log("address", address)],
+        data: Some(to_binary(&HandleAnswer::Kick { status: Success })?),
     })
 }
 
-// Query functions
-
-fn query_pending_rewards<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    address: &HumanAddr,
-    height: u64,
-) -> StdResult<Binary> {
-    let reward_pool = TypedStore::<RewardPool, S>::attach(&deps.storage).load(REWARD_POOL_KEY)?;
-    let user = TypedStore::<UserInfo, S>::attach(&deps.storage)
-        .load(address.0.as_bytes())
-        .unwrap_or(UserInfo { locked: 0, debt: 0 });
+/// Compounds the rewards of every user that opted in to auto compounding. Anyone can call this
+fn compound_all<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    start: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<HandleResponse> {
     let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
-    let mut acc_reward_per_share = reward_pool.acc_reward_per_share;
+    let token_idx = compounding_token_idx(&config)?;
+    enforce_deposits(&config)?;
 
-    if height > reward_pool.last_reward_block
-        && reward_pool.last_reward_block < config.deadline
-        && reward_pool.inc_token_supply != 0
-    {
-        let mut height = height;
-        if height > config.deadline {
-            height = config.deadline;
-        }
+    let start = start.unwrap_or(0);
+    let limit = limit.unwrap_or(COMPOUND_ALL_LIMIT);
+    let end = std::cmp::min(compounder_count(&deps.storage), start.saturating_add(limit));
 
-        let blocks_to_go = config.deadline - reward_pool.last_reward_block;
-        let blocks_to_vest = height - reward_pool.last_reward_block;
-        let rewards =
-            (blocks_to_vest as u128) * reward_pool.pending_rewards / (blocks_to_go as u128);
+    let mut reward_pool = update_rewards(deps, &env, &config)?;
+    let mut messages = vec![];
+    let mut compounded = 0;
+    for idx in start..end {
+        let address = load_compounder(&deps.storage, idx)?;
+        let mut user = load_user(&deps.storage, &address, &config)?;
+        if user.locked == 0 {
+            continue;
+        }
 
-        acc_reward_per_share += rewards * REWARD_SCALE / reward_pool.inc_token_supply;
+        let pending = compound_user(
+            &mut user,
+            &mut reward_pool,
+            &config,
+            token_idx,
+            env.block.height,
+        )?;
+        messages.extend(reward_transfer_msgs(&config, &address, &pending)?);
+        TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage)
+            .store(address.0.as_bytes(), &user)?;
+        compounded += 1;
     }
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
 
-    to_binary(&QueryAnswer::Rewards {
-        // This is not necessarily accurate, since we don't validate the block height. It is up to
-        // the UI to display accurate numbers
-        rewards: Uint128(user.locked * acc_reward_per_share / REWARD_SCALE - user.debt),
+    Ok(HandleResponse {
+        messages,
+        log: vec![log("action", "compound_all"), log("compounded", compounded)],
+        data: Some(to_binary(&HandleAnswer::CompoundAll { status: Success })?),
     })
 }
 
-fn query_deposit<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    address: &HumanAddr,
-) -> StdResult<Binary> {
-    let user = TypedStore::attach(&deps.storage)
-        .load(address.0.as_bytes())
-        .unwrap_or(UserInfo { locked: 0, debt: 0 });
+pub fn create_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    entropy: String,
+) -> StdResult<HandleResponse> {
+    let config: Config = TypedStoreMut::attach(&mut deps.storage).load(CONFIG_KEY)?;
+    let prng_seed = config.prng_seed;
 
-    to_binary(&QueryAnswer::Deposit {
-        deposit: Uint128(user.locked * INC_TOKEN_SCALE),
-    })
-}
+    let key = ViewingKey::new(&env, &prng_seed, (&entropy).as_ref());
 
-fn query_claim_block<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
-    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let mut vk_store = PrefixedStorage::new(VIEWING_KEY_KEY, &mut deps.storage);
+    vk_store.set(env.message.sender.0.as_bytes(), &key.to_hashed());
 
-    to_binary(&QueryAnswer::ClaimBlock {
-        height: config.pool_claim_block,
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "create_viewing_key")],
+        data: Some(to_binary(&HandleAnswer::CreateViewingKey { key })?),
     })
 }
 
-fn query_contract_status<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<Binary> {
-    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+pub fn set_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key: String,
+) -> StdResult<HandleResponse> {
+    let vk = ViewingKey(key);
 
-    to_binary(&QueryAnswer::ContractStatus {
-        is_stopped: config.is_stopped,
+    let mut vk_store = PrefixedStorage::new(VIEWING_KEY_KEY, &mut deps.storage);
+    vk_store.set(env.message.sender.0.as_bytes(), &vk.to_hashed());
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "set_viewing_key")],
+        data: Some(to_binary(&HandleAnswer::SetViewingKey { status: Success })?),
     })
 }
 
-fn query_reward_token<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
-    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+pub fn revoke_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit_name: String,
+) -> StdResult<HandleResponse> {
+    let mut revoked_store = PrefixedStorage::multilevel(
+        &[REVOKED_PERMITS_KEY, env.message.sender.0.as_bytes()],
+        &mut deps.storage,
+    );
+    revoked_store.set(permit_name.as_bytes(), &[1]);
 
-    to_binary(&QueryAnswer::RewardToken {
-        token: config.reward_token,
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "revoke_permit")],
+        data: Some(to_binary(&HandleAnswer::RevokePermit { status: Success })?),
     })
 }
 
-fn query_incentivized_token<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<Binary> {
-    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+fn claim_reward_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: Option<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let config_store = TypedStore::attach(&deps.storage);
+    let config: Config = config_store.load(CONFIG_KEY)?;
 
-    to_binary(&QueryAnswer::IncentivizedToken {
-        token: config.inc_token,
+    enforce_role(&deps.storage, Role::Treasury, &env.message.sender)?;
+
+    if env.block.height < config.pool_claim_block {
+        return Err(ContractError::ClaimTooEarly {
+            claim_block: config.pool_claim_block,
+        }
+        .into());
+    }
+
+    let mut reward_pool = update_rewards(deps, &env, &config)?;
+    let reserved = reserved_rewards(&reward_pool, &config, env.block.height)?;
+    release_unreserved_rewards(&mut reward_pool, &config, env.block.height)?;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+
+    let recipient = recipient.unwrap_or(env.message.sender);
+    let mut messages = vec![];
+    let mut logs = vec![log("action", "claim_reward_pool")];
+    for (token, reserved) in config.reward_tokens.into_iter().zip(reserved) {
+        let balance = snip20::balance_query(
+            &deps.querier,
+            env.contract.address.clone(),
+            config.viewing_key.clone(),
+            RESPONSE_BLOCK_SIZE,
+            token.contract_hash.clone(),
+            token.address.clone(),
+        )?;
+        let unallocated = balance.amount.u128().saturating_sub(reserved);
+
+        logs.push(log("reward", format!("{}:{}", token.address, unallocated)));
+        if unallocated > 0 {
+            messages.push(snip20::transfer_msg(
+                recipient.clone(),
+                Uint128(unallocated),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                token.contract_hash,
+                token.address,
+            )?);
+        }
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::ClaimRewardPool {
+            status: Success,
+        })?),
     })
 }
 
-fn query_end_height<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+/// Withdraws the rewards that were lost to rounding
+fn sweep_dust<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: Option<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    enforce_role(&deps.storage, Role::Treasury, &env.message.sender)?;
+    let recipient = recipient.unwrap_or_else(|| env.message.sender.clone());
+    deps.api.canonical_address(&recipient)?;
+
     let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let mut reward_pool = update_rewards(deps, &env, &config)?;
+    let dust: Vec<u128> = reward_pool
+        .rewards
+        .iter_mut()
+        .map(|token_rewards| take_dust(token_rewards, &config))
+        .collect();
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
 
-    to_binary(&QueryAnswer::EndHeight {
-        height: config.deadline,
+    let mut logs = vec![log("action", "sweep_dust")];
+    logs.extend(reward_logs(&config, &dust));
+
+    Ok(HandleResponse {
+        messages: reward_transfer_msgs(&config, &recipient, &dust)?,
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::SweepDust { status: Success })?),
     })
 }
 
-fn query_reward_pool_balance<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<Binary> {
-    let reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+/// Rewards are updated with the old status first, so the change only applies from this block on
+fn set_contract_status<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    status: ContractStatus,
+) -> StdResult<HandleResponse> {
+    enforce_role(&deps.storage, Role::Pauser, &env.message.sender)?;
 
-    to_binary(&QueryAnswer::RewardPoolBalance {
-        balance: Uint128(reward_pool.pending_rewards as u128),
-    })
-}
+    let mut config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    update_rewards(deps, &env, &config)?;
 
-// This is only for Keplr support (Viewing Keys)
-fn query_token_info() -> StdResult<Binary> {
-    to_binary(&QueryAnswer::TokenInfo {
-        name: "ETH Bridge Rewards".to_string(),
-        symbol: "ETH-RWRDS".to_string(),
-        decimals: 1,
-        total_supply: None,
+    config.status = status;
+    TypedStoreMut::attach(&mut deps.storage).store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "set_contract_status"),
+            log("status", format!("{:?}", status)),
+        ],
+        data: Some(to_binary(&HandleAnswer::SetContractStatus {
+            status: Success,
+        })?),
     })
 }
 
-// Helper functions
+/// Proposes a new admin. The current admin keeps its rights until the new one accepts
+fn propose_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    deps.api.canonical_address(&address)?;
 
-fn enforce_admin(config: Config, env: Env) -> StdResult<()> {
-    if config.admin != env.message.sender {
-        return Err(StdError::generic_err(format!(
-            "not an admin: {}",
-            env.message.sender
-        )));
-    }
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
 
-    Ok(())
+    enforce_admin(config.clone(), env)?;
+
+    config.pending_admin = Some(address.clone());
+    config_store.store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "propose_admin"),
+            log("pending_admin", address),
+        ],
+        data: Some(to_binary(&HandleAnswer::ProposeAdmin { status: Success })?),
+    })
 }
 
-fn update_rewards<S: Storage, A: Api, Q: Querier>(
+/// The roles of the outgoing admin move to the new admin, so a retired admin key keeps none of them
+fn accept_admin<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: &Env,
-    config: &Config,
-) -> StdResult<RewardPool> {
-    let mut rewards_store = TypedStoreMut::attach(&mut deps.storage);
-    let mut reward_pool: RewardPool = rewards_store.load(REWARD_POOL_KEY)?;
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let mut config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
 
-    let mut block = env.block.height;
-    if block > config.deadline {
-        block = config.deadline;
+    if config.pending_admin.as_ref() != Some(&env.message.sender) {
+        return Err(ContractError::NotPendingAdmin {
+            address: env.message.sender,
+        }
+        .into());
     }
 
-    if block <= reward_pool.last_reward_block || reward_pool.last_reward_block >= config.deadline {
-        return Ok(reward_pool);
+    for role in Role::ALL.iter() {
+        let mut members = load_role_members(&deps.storage, *role);
+        if members.contains(&config.admin) {
+            members.retain(|member| *member != config.admin && *member != env.message.sender);
+            members.push(env.message.sender.clone());
+            store_role_members(&mut deps.storage, *role, &members)?;
+        }
     }
 
-    if reward_pool.inc_token_supply == 0 || reward_pool.pending_rewards == 0 {
-        reward_pool.last_reward_block = block;
-        rewards_store.store(REWARD_POOL_KEY, &reward_pool)?;
-        return Ok(reward_pool);
-    }
+    config.admin = env.message.sender;
+    config.pending_admin = None;
+    TypedStoreMut::attach(&mut deps.storage).store(CONFIG_KEY, &config)?;
 
-    let blocks_to_go = config.deadline - reward_pool.last_reward_block;
-    let blocks_to_vest = block - reward_pool.last_reward_block;
-    let rewards = (blocks_to_vest as u128) * reward_pool.pending_rewards / (blocks_to_go as u128);
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "accept_admin"), log("admin", &config.admin)],
+        data: Some(to_binary(&HandleAnswer::AcceptAdmin { status: Success })?),
+    })
+}
 
-    reward_pool.acc_reward_per_share += rewards * REWARD_SCALE / reward_pool.inc_token_supply;
-    reward_pool.pending_rewards -= rewards;
-    reward_pool.last_reward_block = block;
-    rewards_store.store(REWARD_POOL_KEY, &reward_pool)?;
+fn cancel_admin_proposal<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
 
-    Ok(reward_pool)
+    enforce_admin(config.clone(), env)?;
+
+    config.pending_admin = None;
+    config_store.store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "cancel_admin_proposal")],
+        data: Some(to_binary(&HandleAnswer::CancelAdminProposal {
+            status: Success,
+        })?),
+    })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::msg::HandleMsg::{Receive, Redeem, SetViewingKey};
-    use crate::msg::QueryMsg::{Deposit, Rewards};
-    use crate::msg::ReceiveMsg;
-    use crate::state::Snip20;
-    use cosmwasm_std::testing::{
-        mock_dependencies, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
-    };
-    use cosmwasm_std::{
-        coins, from_binary, BlockInfo, Coin, ContractInfo, Empty, MessageInfo, StdError, WasmMsg,
-    };
-    use rand::Rng;
-    use serde::{Deserialize, Serialize};
+fn grant_role<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    role: Role,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    deps.api.canonical_address(&address)?;
+    enforce_role_admin(&deps.storage, &env.message.sender)?;
 
-    // Helper functions
+    let mut members = load_role_members(&deps.storage, role);
+    if !members.contains(&address) {
+        members.push(address.clone());
+    }
+    store_role_members(&mut deps.storage, role, &members)?;
 
-    fn init_helper(
-        deadline: u64,
-    ) -> (
-        StdResult<InitResponse>,
-        Extern<MockStorage, MockApi, MockQuerier>,
-    ) {
-        let mut deps = mock_dependencies(20, &[]);
-        let env = mock_env("admin", &[], 1);
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "grant_role"),
+            log("role", format!("{:?}", role)),
+            log("address", address),
+        ],
+        data: Some(to_binary(&HandleAnswer::GrantRole { status: Success })?),
+    })
+}
 
-        let init_msg = InitMsg {
-            reward_token: Snip20 {
+fn revoke_role<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    role: Role,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    enforce_role_admin(&deps.storage, &env.message.sender)?;
+
+    let mut members = load_role_members(&deps.storage, role);
+    members.retain(|member| *member != address);
+    store_role_members(&mut deps.storage, role, &members)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "revoke_role"),
+            log("role", format!("{:?}", role)),
+            log("address", address),
+        ],
+        data: Some(to_binary(&HandleAnswer::RevokeRole { status: Success })?),
+    })
+}
+
+/// Pays back everything the user has in the contract, without paying any rewards. The rewards earned so far are
+/// kept as `UserInfo::frozen_rewards`
+fn emergency_redeem<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: Option<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let recipient = recipient.unwrap_or_else(|| env.message.sender.clone());
+    deps.api.canonical_address(&recipient)?;
+    let mut user = load_user(&deps.storage, &env.message.sender, &config)?;
+
+    // Rewards don't accrue in this status, so this only settles what was earned before the stop
+    let mut reward_pool = update_rewards(deps, &env, &config)?;
+    let frozen_rewards = pending_rewards(&user, &reward_pool, &config)?;
+
+    // Tokens that are still unbonding are released as well
+    let unbonding = total_unbonding(&load_unbondings(&deps.storage, &env.message.sender))?;
+    store_unbondings(&mut deps.storage, &env.message.sender, vec![])?;
+
+    reward_pool.inc_token_supply = reward_pool
+        .inc_token_supply
+        .checked_sub(user.shares)
+        .ok_or(ContractError::Underflow {})?;
+    reward_pool.inc_token_locked = reward_pool
+        .inc_token_locked
+        .checked_sub(user.locked)
+        .ok_or(ContractError::Underflow {})?;
+    reward_pool.inc_token_unbonding = reward_pool
+        .inc_token_unbonding
+        .checked_sub(unbonding)
+        .ok_or(ContractError::Underflow {})?;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+
+    let amount = user
+        .locked
+        .checked_add(unbonding)
+        .ok_or(ContractError::Overflow {})?;
+    let mut messages = vec![];
+    if amount > 0 {
+        messages.push(secret_toolkit::snip20::transfer_msg(
+            recipient,
+            Uint128(amount),
+            None,
+            RESPONSE_BLOCK_SIZE,
+            config.inc_token.contract_hash,
+            config.inc_token.address,
+        )?);
+    }
+
+    user = UserInfo {
+        auto_compound: user.auto_compound,
+        frozen_rewards,
+        ..UserInfo::new(config.reward_tokens.len())
+    };
+    TypedStoreMut::attach(&mut deps.storage).store(env.message.sender.0.as_bytes(), &user)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![log("action", "emergency_redeem"), log("amount", amount)],
+        data: Some(to_binary(&HandleAnswer::EmergencyRedeem {
+            status: Success,
+        })?),
+    })
+}
+
+/// Replaces the addresses that may deposit rewards. `None` lets anyone deposit
+fn set_allowed_funders<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    funders: Option<Vec<HumanAddr>>,
+) -> StdResult<HandleResponse> {
+    enforce_role(&deps.storage, Role::CampaignManager, &env.message.sender)?;
+    if let Some(funders) = &funders {
+        for funder in funders {
+            deps.api.canonical_address(funder)?;
+        }
+    }
+
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
+    let restricted = funders.is_some();
+    config.allowed_funders = funders;
+    config_store.store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "set_allowed_funders"),
+            log("restricted", restricted),
+        ],
+        data: Some(to_binary(&HandleAnswer::SetAllowedFunders {
+            status: Success,
+        })?),
+    })
+}
+
+/// Pays out the rewards a user kept after an emergency redeem, in case the contract won't resume soon
+fn pay_frozen_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    enforce_role(&deps.storage, Role::Treasury, &env.message.sender)?;
+
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let mut user = load_user(&deps.storage, &address, &config)?;
+
+    let frozen_rewards = std::mem::replace(
+        &mut user.frozen_rewards,
+        vec![0; config.reward_tokens.len()],
+    );
+    TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage).store(address.0.as_bytes(), &user)?;
+
+    let mut reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+    settle_unclaimed(&mut reward_pool, &config, &frozen_rewards)?;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+
+    Ok(HandleResponse {
+        messages: reward_transfer_msgs(&config, &address, &frozen_rewards)?,
+        log: vec![log("action", "pay_frozen_rewards"), log("address", address)],
+        data: Some(to_binary(&HandleAnswer::PayFrozenRewards {
+            status: Success,
+        })?),
+    })
+}
+
+/// Moves the end of the schedule that ends last
+fn set_deadline<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    height: u64,
+) -> StdResult<HandleResponse> {
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    enforce_role(&deps.storage, Role::CampaignManager, &env.message.sender)?;
+    let mut reward_pool = update_rewards(deps, &env, &config)?;
+
+    let schedule_idx = last_schedule_idx(&reward_pool);
+    let start = std::cmp::max(reward_pool.schedules[schedule_idx].start, env.block.height);
+    if height <= start {
+        return Err(ContractError::InvalidSchedule { start, end: height }.into());
+    }
+
+    reward_pool.schedules[schedule_idx].end = height;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "set_deadline"), log("deadline", height)],
+        data: Some(to_binary(&HandleAnswer::SetDeadline { status: Success })?),
+    })
+}
+
+/// Moves the block from which rewards are distributed. This is only possible before the campaign starts
+fn set_start_block<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    height: u64,
+) -> StdResult<HandleResponse> {
+    let mut config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    enforce_role(&deps.storage, Role::CampaignManager, &env.message.sender)?;
+
+    if env.block.height >= config.start_block {
+        return Err(ContractError::RewardsStarted {
+            start_block: config.start_block,
+        }
+        .into());
+    }
+    if height < env.block.height {
+        return Err(ContractError::StartBlockInPast { block: height }.into());
+    }
+
+    // Schedules that start with the campaign got the old start block, so they move along with it
+    let mut reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+    for schedule in reward_pool
+        .schedules
+        .iter_mut()
+        .filter(|schedule| schedule.start <= config.start_block)
+    {
+        if schedule.end <= height {
+            return Err(ContractError::InvalidSchedule {
+                start: height,
+                end: schedule.end,
+            }
+            .into());
+        }
+        schedule.start = height;
+    }
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+
+    config.start_block = height;
+    TypedStoreMut::<Config, S>::attach(&mut deps.storage).store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "set_start_block"), log("start_block", height)],
+        data: Some(to_binary(&HandleAnswer::SetStartBlock { status: Success })?),
+    })
+}
+
+// Query functions
+
+fn query_pending_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    height: u64,
+) -> StdResult<Binary> {
+    let mut reward_pool =
+        TypedStore::<RewardPool, S>::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    let user = load_user(&deps.storage, address, &config)?;
+
+    if height < reward_pool.last_reward_block {
+        return Err(ContractError::HeightTooOld {
+            height,
+            last_reward_block: reward_pool.last_reward_block,
+        }
+        .into());
+    }
+    if height - reward_pool.last_reward_block > MAX_REWARDS_QUERY_LOOKAHEAD
+        && reward_pool
+            .schedules
+            .iter()
+            .any(|schedule| schedule.end > reward_pool.last_reward_block)
+    {
+        return Err(ContractError::HeightTooFarAhead {
+            height,
+            last_reward_block: reward_pool.last_reward_block,
+        }
+        .into());
+    }
+
+    let last_reward_block = reward_pool.last_reward_block;
+    accrue_rewards(&mut reward_pool, &config, height)?;
+    let pending = pending_rewards(&user, &reward_pool, &config)?;
+
+    to_binary(&QueryAnswer::Rewards {
+        rewards: reward_amounts(&config, pending),
+        height,
+        last_reward_block,
+    })
+}
+
+fn query_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let user = load_user(&deps.storage, address, &config)?;
+
+    to_binary(&QueryAnswer::Deposit {
+        deposit: Uint128(user.locked),
+        unlock_height: user.unlock_height,
+        boost: user.boost,
+    })
+}
+
+fn query_unbondings<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let unbondings = load_unbondings(&deps.storage, address);
+
+    to_binary(&QueryAnswer::Unbondings {
+        unbondings: unbondings
+            .into_iter()
+            .map(|unbonding| UnbondingInfo {
+                amount: Uint128(unbonding.amount),
+                release_height: unbonding.release_height,
+            })
+            .collect(),
+    })
+}
+
+fn query_claim_block<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&QueryAnswer::ClaimBlock {
+        height: config.pool_claim_block,
+    })
+}
+
+fn query_contract_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&QueryAnswer::ContractStatus {
+        status: config.status,
+    })
+}
+
+fn query_pending_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&QueryAnswer::PendingAdmin {
+        address: config.pending_admin,
+    })
+}
+
+fn query_roles<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    to_binary(&QueryAnswer::Roles {
+        roles: Role::ALL
+            .iter()
+            .map(|role| RoleInfo {
+                role: *role,
+                addresses: load_role_members(&deps.storage, *role),
+            })
+            .collect(),
+    })
+}
+
+fn query_allowed_funders<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&QueryAnswer::AllowedFunders {
+        funders: config.allowed_funders,
+    })
+}
+
+/// Returns the reward deposits from the oldest, starting at index `start`
+fn query_funding_history<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let fundings_store = ReadonlyPrefixedStorage::new(FUNDINGS_KEY, &deps.storage);
+    let fundings_store = TypedStore::<Funding, _>::attach(&fundings_store);
+    let total = funding_count(&deps.storage);
+    let start = start.unwrap_or(0);
+    let limit = std::cmp::min(
+        limit.unwrap_or(FUNDING_HISTORY_LIMIT),
+        FUNDING_HISTORY_LIMIT,
+    );
+    let end = std::cmp::min(total, start.saturating_add(limit));
+
+    let fundings = (start..end)
+        .map(|idx| -> StdResult<FundingInfo> {
+            let funding = fundings_store.load(&idx.to_be_bytes())?;
+            Ok(FundingInfo {
+                funder: funding.funder,
+                token: funding.token,
+                amount: Uint128(funding.amount),
+                block: funding.block,
+            })
+        })
+        .collect::<StdResult<Vec<FundingInfo>>>()?;
+
+    to_binary(&QueryAnswer::FundingHistory { fundings, total })
+}
+
+fn query_reward_tokens<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&QueryAnswer::RewardTokens {
+        tokens: config.reward_tokens,
+    })
+}
+
+fn query_incentivized_token<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&QueryAnswer::IncentivizedToken {
+        token: config.inc_token,
+    })
+}
+
+fn query_start_height<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&QueryAnswer::StartHeight {
+        height: config.start_block,
+    })
+}
+
+fn query_end_height<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+
+    to_binary(&QueryAnswer::EndHeight {
+        height: end_height(&reward_pool),
+    })
+}
+
+fn query_reward_pool_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&QueryAnswer::RewardPoolBalance {
+        balances: reward_amounts(
+            &config,
+            reward_pool
+                .rewards
+                .iter()
+                .map(|token_rewards| token_rewards.pending_rewards)
+                .collect(),
+        ),
+    })
+}
+
+fn query_reward_dust<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&QueryAnswer::RewardDust {
+        dust: reward_amounts(
+            &config,
+            reward_pool
+                .rewards
+                .iter()
+                .map(|token_rewards| token_rewards.dust / config.reward_scale)
+                .collect(),
+        ),
+    })
+}
+
+/// Returns the schedules that match `filter`. Amounts are the rewards left as of the last reward update
+fn query_schedules<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    filter: impl Fn(&RewardSchedule) -> bool,
+) -> StdResult<Binary> {
+    let reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&QueryAnswer::RewardSchedules {
+        schedules: reward_pool
+            .schedules
+            .into_iter()
+            .filter(|schedule| filter(schedule))
+            .map(|schedule| RewardScheduleInfo {
+                start: schedule.start,
+                end: schedule.end,
+                rewards: reward_amounts(&config, schedule.amounts),
+            })
+            .collect(),
+        last_reward_block: reward_pool.last_reward_block,
+    })
+}
+
+/// The locked tokens are this contract's SNIP-20 token, so wallets can show them and set viewing keys.
+/// Its name, symbol and decimals are derived from the incentivized token's
+fn query_token_info<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+    let inc_token_info = snip20::token_info_query(
+        &deps.querier,
+        RESPONSE_BLOCK_SIZE,
+        config.inc_token.contract_hash,
+        config.inc_token.address,
+    )?;
+
+    to_binary(&QueryAnswer::TokenInfo {
+        name: format!("Locked {}", inc_token_info.name),
+        symbol: format!("L{}", inc_token_info.symbol),
+        decimals: inc_token_info.decimals,
+        total_supply: Some(Uint128(reward_pool.inc_token_locked)),
+    })
+}
+
+fn query_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let user = load_user(&deps.storage, address, &config)?;
+
+    to_binary(&QueryAnswer::Balance {
+        amount: Uint128(user.locked),
+    })
+}
+
+fn query_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: &HumanAddr,
+    spender: &HumanAddr,
+) -> StdResult<Binary> {
+    let allowance = load_allowance(&deps.storage, owner, spender);
+
+    to_binary(&QueryAnswer::Allowance {
+        owner: owner.clone(),
+        spender: spender.clone(),
+        allowance: Uint128(allowance.amount),
+        expiration: allowance.expiration,
+    })
+}
+
+// Helper functions
+
+fn enforce_admin(config: Config, env: Env) -> StdResult<()> {
+    if config.admin != env.message.sender {
+        return Err(ContractError::Unauthorized {
+            address: env.message.sender,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Compounding counts as a deposit as well, since it locks more tokens
+fn enforce_deposits(config: &Config) -> StdResult<()> {
+    if !config.status.accepts_deposits() {
+        return Err(ContractError::ContractStopped {
+            status: config.status,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+fn enforce_role<S: ReadonlyStorage>(storage: &S, role: Role, address: &HumanAddr) -> StdResult<()> {
+    if !load_role_members(storage, role).contains(address) {
+        return Err(ContractError::MissingRole {
+            address: address.clone(),
+            role,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// The admin can always manage roles, so the contract can't be left without anyone to grant them
+fn enforce_role_admin<S: ReadonlyStorage>(storage: &S, address: &HumanAddr) -> StdResult<()> {
+    let config: Config = TypedStore::attach(storage).load(CONFIG_KEY)?;
+    if config.admin == *address {
+        return Ok(());
+    }
+
+    enforce_role(storage, Role::RoleAdmin, address)
+}
+
+/// Loads a user's lockup, or a new one if there's none. Lockups of the first release are upgraded on the way
+fn load_user<S: ReadonlyStorage>(
+    storage: &S,
+    address: &HumanAddr,
+    config: &Config,
+) -> StdResult<UserInfo> {
+    let key = address.0.as_bytes();
+    match TypedStore::<UserInfo, S>::attach(storage).load(key) {
+        Ok(user) => Ok(user),
+        Err(_) => match TypedStore::<v0::UserInfo, S>::attach(storage).load(key) {
+            Ok(user) => upgrade_user(user),
+            Err(_) => Ok(UserInfo::new(config.reward_tokens.len())), // NotFound is the only possible error
+        },
+    }
+}
+
+fn load_role_members<S: ReadonlyStorage>(storage: &S, role: Role) -> Vec<HumanAddr> {
+    let roles_store = ReadonlyPrefixedStorage::new(ROLES_KEY, storage);
+    TypedStore::<Vec<HumanAddr>, _>::attach(&roles_store)
+        .load(role.storage_key())
+        .unwrap_or_default() // NotFound is the only possible error
+}
+
+fn store_role_members<S: Storage>(
+    storage: &mut S,
+    role: Role,
+    members: &[HumanAddr],
+) -> StdResult<()> {
+    let mut roles_store = PrefixedStorage::new(ROLES_KEY, storage);
+    TypedStoreMut::<Vec<HumanAddr>, _>::attach(&mut roles_store)
+        .store(role.storage_key(), &members.to_vec())
+}
+
+fn update_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    config: &Config,
+) -> StdResult<RewardPool> {
+    let mut rewards_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut reward_pool: RewardPool = rewards_store.load(REWARD_POOL_KEY)?;
+
+    if env.block.height <= reward_pool.last_reward_block {
+        return Ok(reward_pool);
+    }
+
+    accrue_rewards(&mut reward_pool, config, env.block.height)?;
+    rewards_store.store(REWARD_POOL_KEY, &reward_pool)?;
+
+    Ok(reward_pool)
+}
+
+/// Distributes the rewards of every schedule between the last reward block and `height`. Each schedule spreads
+/// what it has left linearly until its end, so blocks without any locked shares, before `Config::start_block` or
+/// while rewards are paused push the rewards forward instead of losing them
+fn accrue_rewards(reward_pool: &mut RewardPool, config: &Config, height: u64) -> StdResult<()> {
+    if height <= reward_pool.last_reward_block {
+        return Ok(());
+    }
+
+    if reward_pool.inc_token_supply != 0 && config.status.distributes_rewards() {
+        for schedule in reward_pool.schedules.iter_mut() {
+            let from = *[
+                reward_pool.last_reward_block,
+                schedule.start,
+                config.start_block,
+            ]
+            .iter()
+            .max()
+            .unwrap();
+            let to = std::cmp::min(height, schedule.end);
+            if to <= from {
+                continue;
+            }
+
+            let blocks_to_go = (schedule.end - from) as u128;
+            let blocks_to_vest = (to - from) as u128;
+            for (amount, token_rewards) in schedule
+                .amounts
+                .iter_mut()
+                .zip(reward_pool.rewards.iter_mut())
+            {
+                let rewards = blocks_to_vest
+                    .checked_mul(*amount)
+                    .ok_or(ContractError::Overflow {})?
+                    / blocks_to_go;
+                let scaled_rewards = rewards
+                    .checked_mul(config.reward_scale)
+                    .ok_or(ContractError::Overflow {})?;
+                let rewards_per_share = scaled_rewards / reward_pool.inc_token_supply;
+
+                // Kept scaled, so that fractions lost on every update add up exactly
+                token_rewards.dust = token_rewards
+                    .dust
+                    .checked_add(scaled_rewards % reward_pool.inc_token_supply)
+                    .ok_or(ContractError::Overflow {})?;
+
+                token_rewards.unclaimed = token_rewards
+                    .unclaimed
+                    .checked_add(scaled_rewards - scaled_rewards % reward_pool.inc_token_supply)
+                    .ok_or(ContractError::Overflow {})?;
+                token_rewards.acc_reward_per_share = token_rewards
+                    .acc_reward_per_share
+                    .checked_add(rewards_per_share)
+                    .ok_or(ContractError::Overflow {})?;
+                token_rewards.pending_rewards = token_rewards
+                    .pending_rewards
+                    .checked_sub(rewards)
+                    .ok_or(ContractError::Underflow {})?;
+                *amount -= rewards; // Never more than `amount`, since `blocks_to_vest <= blocks_to_go`
+            }
+        }
+    }
+
+    // Finished schedules are dropped, except for the last one which can still be extended. What they didn't
+    // distribute moves to the last one, so it isn't lost
+    let last_idx = last_schedule_idx(reward_pool);
+    let mut leftovers = vec![0u128; reward_pool.rewards.len()];
+    for (idx, schedule) in reward_pool.schedules.iter().enumerate() {
+        if idx != last_idx && schedule.end <= height {
+            for (leftover, amount) in leftovers.iter_mut().zip(&schedule.amounts) {
+                *leftover = leftover
+                    .checked_add(*amount)
+                    .ok_or(ContractError::Overflow {})?;
+            }
+        }
+    }
+    for (amount, leftover) in reward_pool.schedules[last_idx]
+        .amounts
+        .iter_mut()
+        .zip(leftovers)
+    {
+        *amount = amount
+            .checked_add(leftover)
+            .ok_or(ContractError::Overflow {})?;
+    }
+    let mut idx = 0;
+    reward_pool.schedules.retain(|schedule| {
+        let keep = schedule.end > height || idx == last_idx;
+        idx += 1;
+        keep
+    });
+    reward_pool.last_reward_block = height;
+
+    Ok(())
+}
+
+/// Returns the end of the schedule that ends last
+fn end_height(reward_pool: &RewardPool) -> u64 {
+    reward_pool.schedules[last_schedule_idx(reward_pool)].end
+}
+
+fn last_schedule_idx(reward_pool: &RewardPool) -> usize {
+    let mut last = 0;
+    for (i, schedule) in reward_pool.schedules.iter().enumerate() {
+        if schedule.end >= reward_pool.schedules[last].end {
+            last = i;
+        }
+    }
+
+    last
+}
+
+/// Returns the index of the schedule with the given blocks, adding it if it doesn't exist yet
+fn add_schedule(
+    reward_pool: &mut RewardPool,
+    reward_tokens: usize,
+    start: u64,
+    end: u64,
+) -> StdResult<usize> {
+    if let Some(idx) = reward_pool
+        .schedules
+        .iter()
+        .position(|schedule| schedule.start == start && schedule.end == end)
+    {
+        return Ok(idx);
+    }
+    if reward_pool.schedules.len() >= MAX_REWARD_SCHEDULES {
+        return Err(ContractError::TooManySchedules {
+            max: MAX_REWARD_SCHEDULES,
+        }
+        .into());
+    }
+
+    let idx = reward_pool
+        .schedules
+        .iter()
+        .position(|schedule| schedule.start > start)
+        .unwrap_or_else(|| reward_pool.schedules.len());
+    reward_pool.schedules.insert(
+        idx,
+        RewardSchedule {
+            start,
+            end,
+            amounts: vec![0; reward_tokens],
+        },
+    );
+
+    Ok(idx)
+}
+
+/// Removes paid out rewards from the rewards the users are still owed
+fn settle_unclaimed(reward_pool: &mut RewardPool, config: &Config, paid: &[u128]) -> StdResult<()> {
+    for (token_rewards, paid) in reward_pool.rewards.iter_mut().zip(paid) {
+        let paid = paid
+            .checked_mul(config.reward_scale)
+            .ok_or(ContractError::Overflow {})?;
+        token_rewards.unclaimed = token_rewards
+            .unclaimed
+            .checked_sub(paid)
+            .ok_or(ContractError::Underflow {})?;
+    }
+
+    Ok(())
+}
+
+/// The balance of every reward token the contract must keep: rewards owed to users, rewards of schedules that
+/// are still running, and the principal and unbonding tokens when the reward token is the incentivized token
+fn reserved_rewards(
+    reward_pool: &RewardPool,
+    config: &Config,
+    height: u64,
+) -> StdResult<Vec<u128>> {
+    let mut reserved = reward_pool
+        .rewards
+        .iter()
+        .map(|token_rewards| {
+            // Rounded up, since the fractions belong to the users as well
+            token_rewards.unclaimed / config.reward_scale
+                + (token_rewards.unclaimed % config.reward_scale != 0) as u128
+        })
+        .collect::<Vec<u128>>();
+    for schedule in reward_pool.schedules.iter().filter(|s| s.end > height) {
+        for (reserved, amount) in reserved.iter_mut().zip(&schedule.amounts) {
+            *reserved = reserved
+                .checked_add(*amount)
+                .ok_or(ContractError::Overflow {})?;
+        }
+    }
+    if let Some(idx) = config
+        .reward_tokens
+        .iter()
+        .position(|token| token.address == config.inc_token.address)
+    {
+        reserved[idx] = reserved[idx]
+            .checked_add(reward_pool.inc_token_locked)
+            .and_then(|reserved| reserved.checked_add(reward_pool.inc_token_unbonding))
+            .ok_or(ContractError::Overflow {})?;
+    }
+
+    Ok(reserved)
+}
+
+/// Drops the rewards that can't be distributed anymore, which are the dust and the amounts of schedules that
+/// already ended
+fn release_unreserved_rewards(
+    reward_pool: &mut RewardPool,
+    config: &Config,
+    height: u64,
+) -> StdResult<()> {
+    for schedule in reward_pool.schedules.iter_mut().filter(|s| s.end <= height) {
+        for (amount, token_rewards) in schedule
+            .amounts
+            .iter_mut()
+            .zip(reward_pool.rewards.iter_mut())
+        {
+            token_rewards.pending_rewards = token_rewards
+                .pending_rewards
+                .checked_sub(std::mem::take(amount))
+                .ok_or(ContractError::Underflow {})?;
+        }
+    }
+    for token_rewards in reward_pool.rewards.iter_mut() {
+        take_dust(token_rewards, config);
+    }
+
+    Ok(())
+}
+
+/// Removes the whole tokens from the dust and returns them. Fractions are kept for later
+fn take_dust(token_rewards: &mut TokenRewards, config: &Config) -> u128 {
+    let dust = token_rewards.dust / config.reward_scale;
+    token_rewards.dust %= config.reward_scale;
+
+    dust
+}
+
+/// Calculates the rewards a user is eligible for but hasn't received yet, for every reward token, including
+/// their frozen rewards. Paying them out must be followed by `reset_debt`. Since the debt is rounded up, the
+/// user never gets more than their exact share
+fn pending_rewards(
+    user: &UserInfo,
+    reward_pool: &RewardPool,
+    config: &Config,
+) -> StdResult<Vec<u128>> {
+    reward_pool
+        .rewards
+        .iter()
+        .zip(user.debt.iter())
+        .zip(user.frozen_rewards.iter())
+        .map(|((token_rewards, debt), frozen)| -> StdResult<u128> {
+            Ok(accumulated_rewards(user.shares, token_rewards, config)?
+                .saturating_sub(*debt) // The rounded up debt can be one more than the rewards
+                .checked_add(*frozen)
+                .ok_or(ContractError::Overflow {})?)
+        })
+        .collect()
+}
+
+fn reset_debt(user: &mut UserInfo, reward_pool: &RewardPool, config: &Config) -> StdResult<()> {
+    let shares = user.shares;
+    user.frozen_rewards = vec![0; reward_pool.rewards.len()];
+    user.debt =
+        reward_pool
+            .rewards
+            .iter()
+            .map(|token_rewards| -> StdResult<u128> {
+                let accumulated = shares
+                    .checked_mul(token_rewards.acc_reward_per_share)
+                    .ok_or(ContractError::Overflow {})?;
+                Ok(accumulated / config.reward_scale
+                    + (accumulated % config.reward_scale != 0) as u128)
+            })
+            .collect::<StdResult<Vec<u128>>>()?;
+
+    Ok(())
+}
+
+/// Rewards accumulated by `shares` since the beginning of the pool
+fn accumulated_rewards(
+    shares: u128,
+    token_rewards: &TokenRewards,
+    config: &Config,
+) -> StdResult<u128> {
+    Ok(shares
+        .checked_mul(token_rewards.acc_reward_per_share)
+        .ok_or(ContractError::Overflow {})?
+        / config.reward_scale)
+}
+
+/// Recalculates the user's weighted shares after `locked` or `boost` changed, and updates the pool's supply
+fn update_shares(
+    user: &mut UserInfo,
+    reward_pool: &mut RewardPool,
+    config: &Config,
+) -> StdResult<()> {
+    let shares = (user.locked / config.inc_token_scale)
+        .checked_mul(user.boost as u128)
+        .ok_or(ContractError::Overflow {})?
+        / BOOST_SCALE as u128;
+    reward_pool.inc_token_supply = reward_pool
+        .inc_token_supply
+        .checked_sub(user.shares)
+        .ok_or(ContractError::Underflow {})?
+        .checked_add(shares)
+        .ok_or(ContractError::Overflow {})?;
+    user.shares = shares;
+
+    Ok(())
+}
+
+fn lock_boost(config: &Config, lock_blocks: u64) -> u64 {
+    if config.max_lock_blocks == 0 {
+        return BOOST_SCALE;
+    }
+
+    let lock_blocks = std::cmp::min(lock_blocks, config.max_lock_blocks);
+    BOOST_SCALE
+        + ((config.max_boost - BOOST_SCALE) as u128 * lock_blocks as u128
+            / config.max_lock_blocks as u128) as u64
+}
+
+/// Adds `amount` to the user's locked tokens. The user's boost becomes the average of the current boost and
+/// `boost`, weighted by the amounts they apply to
+fn add_locked(user: &mut UserInfo, amount: u128, boost: u64) -> StdResult<()> {
+    let locked = user
+        .locked
+        .checked_add(amount)
+        .ok_or(ContractError::Overflow {})?;
+    if amount > 0 {
+        let weighted_boost = user
+            .locked
+            .checked_mul(user.boost as u128)
+            .and_then(|total| total.checked_add(amount.checked_mul(boost as u128)?))
+            .ok_or(ContractError::Overflow {})?;
+        user.boost = (weighted_boost / locked) as u64;
+    }
+    user.locked = locked;
+
+    Ok(())
+}
+
+/// Restakes the user's pending rewards of the incentivized token, and returns the pending rewards of the other
+/// reward tokens, which should be paid out
+fn compound_user(
+    user: &mut UserInfo,
+    reward_pool: &mut RewardPool,
+    config: &Config,
+    token_idx: usize,
+    height: u64,
+) -> StdResult<Vec<u128>> {
+    let mut pending = pending_rewards(user, reward_pool, config)?;
+    settle_unclaimed(reward_pool, config, &pending)?;
+
+    if user.unlock_height <= height {
+        user.boost = BOOST_SCALE;
+    }
+    // The compounded tokens are locked with the rest of the position, so they're boosted like a deposit
+    let compound_boost = lock_boost(config, user.unlock_height.saturating_sub(height));
+    add_locked(user, pending[token_idx], compound_boost)?;
+    reward_pool.inc_token_locked = reward_pool
+        .inc_token_locked
+        .checked_add(pending[token_idx])
+        .ok_or(ContractError::Overflow {})?;
+    pending[token_idx] = 0;
+    update_shares(user, reward_pool, config)?;
+    reset_debt(user, reward_pool, config)?;
+
+    Ok(pending)
+}
+
+fn compounding_token_idx(config: &Config) -> StdResult<usize> {
+    config
+        .reward_tokens
+        .iter()
+        .position(|token| token.address == config.inc_token.address)
+        .ok_or_else(|| ContractError::CompoundingUnavailable {}.into())
+}
+
+fn reward_amounts(config: &Config, amounts: Vec<u128>) -> Vec<RewardAmount> {
+    config
+        .reward_tokens
+        .iter()
+        .zip(amounts)
+        .map(|(token, amount)| RewardAmount {
+            token: token.address.clone(),
+            amount: Uint128(amount),
+        })
+        .collect()
+}
+
+fn load_unbondings<S: ReadonlyStorage>(storage: &S, address: &HumanAddr) -> Vec<Unbonding> {
+    let unbondings_store = ReadonlyPrefixedStorage::new(UNBONDINGS_KEY, storage);
+    TypedStore::<Vec<Unbonding>, _>::attach(&unbondings_store)
+        .load(address.0.as_bytes())
+        .unwrap_or_default() // NotFound is the only possible error
+}
+
+fn total_unbonding(unbondings: &[Unbonding]) -> StdResult<u128> {
+    unbondings
+        .iter()
+        .try_fold(0u128, |total, unbonding| {
+            total.checked_add(unbonding.amount)
+        })
+        .ok_or_else(|| ContractError::Overflow {}.into())
+}
+
+fn store_unbondings<S: Storage>(
+    storage: &mut S,
+    address: &HumanAddr,
+    unbondings: Vec<Unbonding>,
+) -> StdResult<()> {
+    let mut unbondings_store = PrefixedStorage::new(UNBONDINGS_KEY, storage);
+    TypedStoreMut::<Vec<Unbonding>, _>::attach(&mut unbondings_store)
+        .store(address.0.as_bytes(), &unbondings)
+}
+
+fn load_allowance<S: ReadonlyStorage>(
+    storage: &S,
+    owner: &HumanAddr,
+    spender: &HumanAddr,
+) -> Allowance {
+    let allowances_store =
+        ReadonlyPrefixedStorage::multilevel(&[ALLOWANCES_KEY, owner.0.as_bytes()], storage);
+    TypedStore::<Allowance, _>::attach(&allowances_store)
+        .load(spender.0.as_bytes())
+        .unwrap_or_default() // NotFound is the only possible error
+}
+
+fn store_allowance<S: Storage>(
+    storage: &mut S,
+    owner: &HumanAddr,
+    spender: &HumanAddr,
+    allowance: &Allowance,
+) -> StdResult<()> {
+    let mut allowances_store =
+        PrefixedStorage::multilevel(&[ALLOWANCES_KEY, owner.0.as_bytes()], storage);
+    TypedStoreMut::<Allowance, _>::attach(&mut allowances_store)
+        .store(spender.0.as_bytes(), allowance)
+}
+
+/// An expired allowance counts as zero
+fn spend_allowance<S: Storage>(
+    storage: &mut S,
+    env: &Env,
+    owner: &HumanAddr,
+    spender: &HumanAddr,
+    amount: u128,
+) -> StdResult<()> {
+    let mut allowance = load_allowance(storage, owner, spender);
+    if let Some(expiration) = allowance.expiration {
+        if expiration <= env.block.time {
+            allowance.amount = 0;
+        }
+    }
+
+    allowance.amount =
+        allowance
+            .amount
+            .checked_sub(amount)
+            .ok_or(ContractError::InsufficientAllowance {
+                allowance: allowance.amount,
+                required: amount,
+            })?;
+    store_allowance(storage, owner, spender, &allowance)
+}
+
+fn load_receiver<S: ReadonlyStorage>(storage: &S, address: &HumanAddr) -> Option<String> {
+    let receivers_store = ReadonlyPrefixedStorage::new(RECEIVERS_KEY, storage);
+    TypedStore::<String, _>::attach(&receivers_store)
+        .load(address.0.as_bytes())
+        .ok()
+}
+
+fn funding_count<S: ReadonlyStorage>(storage: &S) -> u32 {
+    let fundings_store = ReadonlyPrefixedStorage::new(FUNDINGS_KEY, storage);
+    TypedStore::<u32, _>::attach(&fundings_store)
+        .load(b"count")
+        .unwrap_or_default() // NotFound is the only possible error
+}
+
+/// Fundings are kept under their index, so the history can be paged without loading all of it
+fn push_funding<S: Storage>(storage: &mut S, funding: &Funding) -> StdResult<()> {
+    let count = funding_count(storage);
+    let mut fundings_store = PrefixedStorage::new(FUNDINGS_KEY, storage);
+    TypedStoreMut::<Funding, _>::attach(&mut fundings_store)
+        .store(&count.to_be_bytes(), funding)?;
+
+    let count = count.checked_add(1).ok_or(ContractError::Overflow {})?;
+    TypedStoreMut::<u32, _>::attach(&mut fundings_store).store(b"count", &count)
+}
+
+fn compounder_count<S: ReadonlyStorage>(storage: &S) -> u32 {
+    let compounders_store = ReadonlyPrefixedStorage::new(COMPOUNDERS_KEY, storage);
+    TypedStore::<u32, _>::attach(&compounders_store)
+        .load(b"count")
+        .unwrap_or_default() // NotFound is the only possible error
+}
+
+fn load_compounder<S: ReadonlyStorage>(storage: &S, idx: u32) -> StdResult<HumanAddr> {
+    let compounders_store = ReadonlyPrefixedStorage::new(COMPOUNDERS_KEY, storage);
+    TypedStore::<HumanAddr, _>::attach(&compounders_store).load(&idx.to_be_bytes())
+}
+
+/// Compounders are kept under their index, and the index under their address, so opting in or out doesn't load
+/// the whole list and `CompoundAll` can page through it
+fn add_compounder<S: Storage>(storage: &mut S, address: &HumanAddr) -> StdResult<()> {
+    let idx = compounder_count(storage);
+    let mut compounders_store = PrefixedStorage::new(COMPOUNDERS_KEY, storage);
+    TypedStoreMut::<HumanAddr, _>::attach(&mut compounders_store)
+        .store(&idx.to_be_bytes(), address)?;
+    let count = idx.checked_add(1).ok_or(ContractError::Overflow {})?;
+    TypedStoreMut::<u32, _>::attach(&mut compounders_store).store(b"count", &count)?;
+
+    let mut indices_store = PrefixedStorage::new(COMPOUNDER_INDICES_KEY, storage);
+    TypedStoreMut::<u32, _>::attach(&mut indices_store).store(address.0.as_bytes(), &idx)
+}
+
+/// Moves the last compounder into the index of the removed one
+fn remove_compounder<S: Storage>(storage: &mut S, address: &HumanAddr) -> StdResult<()> {
+    let indices_store = ReadonlyPrefixedStorage::new(COMPOUNDER_INDICES_KEY, storage);
+    let idx: u32 = TypedStore::attach(&indices_store).load(address.0.as_bytes())?;
+    let last_idx = compounder_count(storage)
+        .checked_sub(1)
+        .ok_or(ContractError::Underflow {})?;
+    let last = load_compounder(storage, last_idx)?;
+
+    let mut compounders_store = PrefixedStorage::new(COMPOUNDERS_KEY, storage);
+    TypedStoreMut::<HumanAddr, _>::attach(&mut compounders_store)
+        .store(&idx.to_be_bytes(), &last)?;
+    compounders_store.remove(&last_idx.to_be_bytes());
+    TypedStoreMut::<u32, _>::attach(&mut compounders_store).store(b"count", &last_idx)?;
+
+    let mut indices_store = PrefixedStorage::new(COMPOUNDER_INDICES_KEY, storage);
+    TypedStoreMut::<u32, _>::attach(&mut indices_store).store(last.0.as_bytes(), &idx)?;
+    indices_store.remove(address.0.as_bytes());
+
+    Ok(())
+}
+
+fn reward_logs(config: &Config, amounts: &[u128]) -> Vec<LogAttribute> {
+    config
+        .reward_tokens
+        .iter()
+        .zip(amounts)
+        .map(|(token, amount)| log("reward", format!("{}:{}", token.address, amount)))
+        .collect()
+}
+
+fn reward_transfer_msgs(
+    config: &Config,
+    recipient: &HumanAddr,
+    amounts: &[u128],
+) -> StdResult<Vec<CosmosMsg>> {
+    let mut messages = vec![];
+    for (token, amount) in config.reward_tokens.iter().zip(amounts) {
+        if *amount > 0 {
+            messages.push(snip20::transfer_msg(
+                recipient.clone(),
+                Uint128(*amount),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                token.contract_hash.clone(),
+                token.address.clone(),
+            )?);
+        }
+    }
+
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::HandleMsg::{Receive, Redeem, SetViewingKey};
+    use crate::msg::QueryMsg::{Deposit, Rewards};
+    use crate::msg::ReceiveMsg;
+    use crate::permit::{PermitParams, PermitSignature, PubKey};
+    use crate::state::Snip20;
+    use cosmwasm_std::testing::{
+        mock_dependencies, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+    };
+    use cosmwasm_std::{
+        coins, from_binary, from_slice, BlockInfo, CanonicalAddr, Coin, ContractInfo, Empty,
+        MessageInfo, QuerierResult, QueryRequest, StdError, WasmMsg, WasmQuery,
+    };
+    use rand::Rng;
+    use serde::{Deserialize, Serialize};
+
+    // Helper functions
+
+    fn init_helper(
+        deadline: u64,
+    ) -> (
+        StdResult<InitResponse>,
+        Extern<MockStorage, MockApi, MockQuerier>,
+    ) {
+        init_helper_with_reward_tokens(deadline, vec!["scrt"])
+    }
+
+    fn init_helper_with_reward_tokens(
+        deadline: u64,
+        reward_tokens: Vec<&str>,
+    ) -> (
+        StdResult<InitResponse>,
+        Extern<MockStorage, MockApi, MockQuerier>,
+    ) {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("admin", &[], 1);
+
+        (
+            init(&mut deps, env, init_msg(deadline, reward_tokens)),
+            deps,
+        )
+    }
+
+    fn init_msg(deadline: u64, reward_tokens: Vec<&str>) -> InitMsg {
+        InitMsg {
+            reward_tokens: reward_tokens
+                .into_iter()
+                .map(|token| Snip20 {
+                    address: HumanAddr(token.to_string()),
+                    contract_hash: "1".to_string(),
+                })
+                .collect(),
+            inc_token: Snip20 {
+                address: HumanAddr("eth".to_string()),
+                contract_hash: "2".to_string(),
+            },
+            inc_token_scale: Uint128(1_000_000_000_000),
+            reward_scale: Uint128(1_000_000_000_000),
+            max_lock_blocks: 1000,
+            max_boost: 2 * BOOST_SCALE,
+            unbonding_period: 0,
+            start_block: 0,
+            deadline,
+            pool_claim_block: deadline + 1,
+            allowed_funders: None,
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            viewing_key: "123".to_string(),
+        }
+    }
+
+    /// Just set sender and sent funds for the message. The rest uses defaults.
+    /// The sender will be canonicalized internally to allow developers pasing in human readable senders.
+    /// This is intended for use in test code only.
+    pub fn mock_env<U: Into<HumanAddr>>(sender: U, sent: &[Coin], height: u64) -> Env {
+        Env {
+            block: BlockInfo {
+                height,
+                time: 1_571_797_419,
+                chain_id: "cosmos-testnet-14002".to_string(),
+            },
+            message: MessageInfo {
+                sender: sender.into(),
+                sent_funds: sent.to_vec(),
+            },
+            contract: ContractInfo {
+                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+            },
+            contract_key: Some("".to_string()),
+            contract_code_hash: "".to_string(),
+        }
+    }
+
+    fn msg_from_action<S: Storage, A: Api, Q: Querier>(
+        deps: &Extern<S, A, Q>,
+        action: &str,
+        user: HumanAddr,
+        block: u64,
+    ) -> (HandleMsg, String) {
+        let mut rng = rand::thread_rng();
+        let chance = rng.gen_range(0, 100000);
+
+        match action {
+            "deposit" => {
+                let amount: u128 = rng.gen_range(10e12 as u128, 1000e18 as u128);
+
+                let msg = HandleMsg::Receive {
+                    sender: user.clone(),
+                    from: user,
+                    amount: Uint128(amount),
+                    msg: to_binary(&ReceiveMsg::Deposit {
+                        lock_blocks: None,
+                        beneficiary: None,
+                    })
+                    .unwrap(),
+                };
+
+                (msg, "eth".to_string())
+            }
+            "redeem" => {
+                let amount: u128 = rng.gen_range(1e12 as u128, 1000e18 as u128);
+
+                let msg = HandleMsg::Redeem {
+                    amount: Some(Uint128(amount)),
+                    recipient: None,
+                };
+
+                (msg, user.0)
+            }
+            "deadline" if chance == 42 => {
+                let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+                    .load(REWARD_POOL_KEY)
+                    .unwrap();
+                let current = std::cmp::max(end_height(&reward_pool), block) as f64;
+
+                let new = rng.gen_range(current + 1.0, current * 1.001);
+
+                let msg = HandleMsg::SetDeadline { block: new as u64 };
+
+                (msg, "admin".to_string())
+            }
+            "rewards" if chance == 7 => {
+                let amount: u128 = rng.gen_range(10000e6 as u128, 100000e6 as u128);
+
+                let msg = HandleMsg::Receive {
+                    sender: user.clone(),
+                    from: user,
+                    amount: Uint128(amount),
+                    msg: to_binary(&ReceiveMsg::DepositRewards {
+                        start: None,
+                        end: None,
+                    })
+                    .unwrap(),
+                };
+
+                (msg, "scrt".to_string())
+            }
+            _ => (
+                HandleMsg::Redeem {
+                    amount: Some(Uint128(u128::MAX)), // This will never work but will keep the tests going
+                    recipient: None,
+                },
+                "".to_string(),
+            ),
+        }
+    }
+
+    fn print_status(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        users: Vec<HumanAddr>,
+        block: u64,
+    ) {
+        let reward_pool = TypedStore::<RewardPool, MockStorage>::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
+
+        println!("####### Statistics for block: {} #######", block);
+        println!("Deadline: {}", end_height(&reward_pool));
+        println!("Locked shares: {}", reward_pool.inc_token_supply);
+        for (token, token_rewards) in config.reward_tokens.iter().zip(reward_pool.rewards) {
+            println!("# {}:", token.address.0);
+            println!("Pending rewards: {}", token_rewards.pending_rewards);
+            println!(
+                "Accumulated rewards per share: {}",
+                token_rewards.acc_reward_per_share
+            );
+        }
+        println!("Last reward block: {}", reward_pool.last_reward_block);
+
+        for user in users {
+            println!("## {}:", user.0);
+            let user_info = TypedStore::<UserInfo, MockStorage>::attach(&deps.storage)
+                .load(user.0.as_bytes())
+                .unwrap_or_else(|_| UserInfo::new(config.reward_tokens.len()));
+            let rewards = query_rewards(deps, user.clone(), block);
+
+            println!("Locked: {}", user_info.locked);
+            println!("Debt: {:?}", user_info.debt);
+            println!("Reward: {:?}", rewards);
+        }
+
+        println!();
+    }
+
+    fn query_rewards(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        user: HumanAddr,
+        block: u64,
+    ) -> Vec<u128> {
+        let query_msg = QueryMsg::Rewards {
+            address: user,
+            height: block,
+            key: "42".to_string(),
+        };
+
+        let result: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match result {
+            QueryAnswer::Rewards { rewards, .. } => rewards
+                .into_iter()
+                .map(|reward| reward.amount.u128())
+                .collect(),
+            _ => panic!("NOPE"),
+        }
+    }
+
+    fn set_vks(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, users: Vec<HumanAddr>) {
+        for user in users {
+            let vk_msg = SetViewingKey {
+                key: "42".to_string(),
+                padding: None,
+            };
+            handle(deps, mock_env(user.0, &[], 2001), vk_msg).unwrap();
+        }
+    }
+
+    fn extract_rewards(result: StdResult<HandleResponse>) -> u128 {
+        match result {
+            Ok(resp) => {
+                for message in resp.messages {
+                    match message {
+                        CosmosMsg::Wasm(w) => match w {
+                            WasmMsg::Execute {
+                                contract_addr, msg, ..
+                            } => {
+                                if contract_addr == HumanAddr("scrt".to_string()) {
+                                    let transfer_msg: Snip20HandleMsg = from_binary(&msg).unwrap();
+
+                                    match transfer_msg {
+                                        Snip20HandleMsg::Transfer { amount, .. } => {
+                                            return amount.u128();
+                                        }
+                                        _ => panic!(),
+                                    }
+                                }
+                            }
+                            _ => panic!(),
+                        },
+                        _ => panic!(),
+                    }
+                }
+            }
+            Err(e) => match e {
+                StdError::NotFound { .. } => {}
+                StdError::GenericErr { msg, backtrace } => {
+                    if !msg.contains("insufficient") {
+                        panic!(format!("{}", msg))
+                    }
+                }
+                _ => panic!(format!("{:?}", e)),
+            },
+        }
+
+        0
+    }
+
+    fn extract_transfer(response: &HandleResponse, token: &str) -> u128 {
+        response
+            .messages
+            .iter()
+            .map(|message| match message {
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr, msg, ..
+                }) if contract_addr.0 == token => {
+                    match from_binary::<Snip20HandleMsg>(msg).unwrap() {
+                        Snip20HandleMsg::Transfer { amount, .. } => amount.u128(),
+                    }
+                }
+                _ => 0,
+            })
+            .sum()
+    }
+
+    fn extract_reward_deposit(msg: HandleMsg) -> u128 {
+        match msg {
+            HandleMsg::Receive { amount, msg, .. } => {
+                let transfer_msg: ReceiveMsg = from_binary(&msg).unwrap();
+
+                match transfer_msg {
+                    ReceiveMsg::DepositRewards { .. } => amount.u128(),
+                    _ => 0,
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    fn sanity_run(mut rewards: u128, mut deadline: u64) {
+        let mut rng = rand::thread_rng();
+
+        let (init_result, mut deps) = init_helper(deadline);
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            rewards,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let actions = vec!["deposit", "redeem", "deadline", "rewards"];
+        let users = vec![
+            HumanAddr("Lebron James".to_string()),
+            HumanAddr("Kobe Bryant".to_string()),
+            HumanAddr("Giannis".to_string()),
+            HumanAddr("Steph Curry".to_string()),
+            HumanAddr("Deni Avdija".to_string()),
+        ];
+
+        let mut total_rewards_output = 0;
+
+        set_vks(&mut deps, users.clone());
+        let mut block: u64 = 2;
+        while block < (deadline + 10_000) {
+            let num_of_actions = rng.gen_range(0, 5);
+
+            for i in 0..num_of_actions {
+                let action_idx = rng.gen_range(0, actions.len());
+
+                let user_idx = rng.gen_range(0, users.len());
+                let user = users[user_idx].clone();
+
+                let (msg, sender) =
+                    msg_from_action(&deps, actions[action_idx], user.clone(), block);
+                rewards += extract_reward_deposit(msg.clone());
+                let result = handle(&mut deps, mock_env(sender, &[], block), msg);
+                total_rewards_output += extract_rewards(result);
+            }
+
+            if block % 10000 == 0 {
+                print_status(&deps, users.clone(), block);
+            }
+
+            deadline = end_height(
+                &TypedStore::<RewardPool, MockStorage>::attach(&deps.storage)
+                    .load(REWARD_POOL_KEY)
+                    .unwrap(),
+            );
+            block += 1;
+        }
+
+        // Make sure all users are fully redeemed
+        for user in users {
+            let redeem_msg = HandleMsg::Redeem {
+                amount: None,
+                recipient: None,
+            };
+            let result = handle(&mut deps, mock_env(user.0, &[], 1_700_000), redeem_msg);
+            total_rewards_output += extract_rewards(result);
+        }
+
+        // Whatever was lost to rounding is accounted for as dust, and nothing more
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        let dust = reward_pool.rewards[0].dust / 1_000_000_000_000;
+        assert!(total_rewards_output + dust <= rewards);
+
+        let error = 1.0 - (total_rewards_output as f64 / rewards as f64);
+        println!("Error is: {}", error);
+        assert!(error >= 0f64 && error < 0.01);
+    }
+
+    // Tests
+
+    #[test]
+    fn test_migrate() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+        let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
+
+        let migrate_response = migrate(&mut deps, mock_env("admin", &[], 10), MigrateMsg {});
+        assert_eq!(
+            migrate_response.unwrap().log,
+            vec![
+                log("action", "migrate"),
+                log("from_version", CONTRACT_VERSION),
+                log("to_version", CONTRACT_VERSION),
+            ]
+        );
+        let migrated: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
+        assert_eq!(migrated, config);
+
+        TypedStoreMut::attach(&mut deps.storage)
+            .store(CONTRACT_VERSION_KEY, &(CONTRACT_VERSION + 1))
+            .unwrap();
+        let migrate_response = migrate(&mut deps, mock_env("admin", &[], 10), MigrateMsg {});
+        assert_eq!(
+            migrate_response.unwrap_err(),
+            StdError::from(ContractError::UnknownVersion {
+                version: CONTRACT_VERSION + 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_upgrade_from_v0() {
+        let mut deps = mock_dependencies(20, &[]);
+
+        let legacy_config = v0::Config {
+            admin: HumanAddr("admin".to_string()),
+            reward_token: Snip20 {
                 address: HumanAddr("scrt".to_string()),
                 contract_hash: "1".to_string(),
             },
@@ -722,414 +3115,2563 @@ mod tests {
                 address: HumanAddr("eth".to_string()),
                 contract_hash: "2".to_string(),
             },
-            deadline,
-            pool_claim_block: deadline + 1,
-            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            pool_claim_block: 2000,
+            deadline: 1000,
             viewing_key: "123".to_string(),
+            prng_seed: vec![],
+            is_stopped: true,
+        };
+        let legacy_pool = v0::RewardPool {
+            pending_rewards: 400,
+            inc_token_supply: 3,
+            last_reward_block: 500,
+            acc_reward_per_share: 2_000_000_000_000,
+        };
+        TypedStoreMut::attach(&mut deps.storage)
+            .store(CONFIG_KEY, &legacy_config)
+            .unwrap();
+        TypedStoreMut::attach(&mut deps.storage)
+            .store(REWARD_POOL_KEY, &legacy_pool)
+            .unwrap();
+        let user = HumanAddr("user".to_string());
+        TypedStoreMut::attach(&mut deps.storage)
+            .store(user.0.as_bytes(), &v0::UserInfo { locked: 3, debt: 2 })
+            .unwrap();
+
+        // The upgrade reads the contract's balance of the reward token to find the unclaimed rewards. Besides the
+        // pending rewards and the 6 the users accrued, it holds the 1 SCRT buffers of two reward deposits
+        let mut deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: Snip20Querier { balance: 2_000_406 },
+        };
+        let migrate_response = migrate(&mut deps, mock_env("admin", &[], 500), MigrateMsg {});
+        assert_eq!(
+            migrate_response.unwrap().log,
+            vec![
+                log("action", "migrate"),
+                log("from_version", 0),
+                log("to_version", CONTRACT_VERSION),
+            ]
+        );
+
+        let version: u32 = TypedStore::attach(&deps.storage)
+            .load(CONTRACT_VERSION_KEY)
+            .unwrap();
+        assert_eq!(version, CONTRACT_VERSION);
+
+        let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
+        assert_eq!(
+            config,
+            upgrade_config(legacy_config, HumanAddr(MOCK_CONTRACT_ADDR.to_string()))
+        );
+        assert_eq!(config.status, ContractStatus::EmergencyOnly);
+        assert_eq!(config.contract_address, HumanAddr::from(MOCK_CONTRACT_ADDR));
+
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.inc_token_locked, 3_000_000_000_000);
+        assert_eq!(reward_pool.rewards[0].unclaimed, 6_000_000_000_000);
+        assert_eq!(reward_pool.rewards[0].dust, 2_000_000_000_000_000_000);
+        assert_eq!(
+            reward_pool.schedules,
+            vec![RewardSchedule {
+                start: 0,
+                end: 1000,
+                amounts: vec![400],
+            }]
+        );
+
+        for role in Role::ALL.iter() {
+            assert_eq!(
+                load_role_members(&deps.storage, *role),
+                vec![HumanAddr("admin".to_string())]
+            );
+        }
+
+        // Migrating again is a no-op
+        let migrate_response = migrate(&mut deps, mock_env("admin", &[], 500), MigrateMsg {});
+        assert_eq!(
+            migrate_response.unwrap().log,
+            vec![
+                log("action", "migrate"),
+                log("from_version", CONTRACT_VERSION),
+                log("to_version", CONTRACT_VERSION),
+            ]
+        );
+        let migrated: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(migrated, reward_pool);
+
+        // Lockups are upgraded when they're loaded
+        let mut deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: MockQuerier::new(&[]),
+        };
+        set_vks(&mut deps, vec![user.clone()]);
+        assert_eq!(query_rewards(&deps, user.clone(), 500), vec![4]);
+
+        let query_msg = QueryMsg::Deposit {
+            address: user.clone(),
+            key: "42".to_string(),
+        };
+        let result: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match result {
+            QueryAnswer::Deposit { deposit, .. } => {
+                assert_eq!(deposit, Uint128(3_000_000_000_000))
+            }
+            _ => panic!("NOPE"),
+        }
+
+        // The user can still claim, and the buffers can be swept
+        let status_msg = HandleMsg::SetContractStatus {
+            status: ContractStatus::Normal,
+        };
+        handle(&mut deps, mock_env("admin", &[], 500), status_msg).unwrap();
+        let claim_msg = HandleMsg::ClaimRewards { recipient: None };
+        let handle_response = handle(&mut deps, mock_env(user.0, &[], 500), claim_msg);
+        assert_eq!(extract_rewards(handle_response), 4);
+        let sweep_msg = HandleMsg::SweepDust { to: None };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 500), sweep_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 2_000_000);
+    }
+
+    #[test]
+    fn test_claim_pool() {
+        let (init_result, mut deps) = init_helper(10000000); // Claim height is deadline + 1
+
+        let claim_msg = HandleMsg::ClaimRewardPool { to: None };
+        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 10), claim_msg.clone());
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::MissingRole {
+                address: HumanAddr("not_admin".to_string()),
+                role: Role::Treasury
+            })
+        );
+
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 10), claim_msg.clone());
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::ClaimTooEarly {
+                claim_block: 10000001
+            })
+        );
+
+        let handle_response = handle(
+            &mut deps,
+            mock_env("admin", &[], 10000001),
+            claim_msg.clone(),
+        );
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::GenericErr {
+                msg: "Error performing Balance query: Generic error: Querier system error: No such contract: scrt".to_string(), // No way to test external queries yet
+                backtrace: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_reserved_rewards() {
+        let (init_result, mut deps) = init_helper_with_reward_tokens(1001, vec!["scrt", "eth"]);
+        assert!(init_result.is_ok());
+
+        let mut config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
+        config.unbonding_period = 10;
+        TypedStoreMut::attach(&mut deps.storage)
+            .store(CONFIG_KEY, &config)
+            .unwrap();
+        let reserved = |deps: &Extern<MockStorage, MockApi, MockQuerier>, height: u64| {
+            let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+                .load(REWARD_POOL_KEY)
+                .unwrap();
+            reserved_rewards(&reward_pool, &config, height).unwrap()
+        };
+
+        let funder = HumanAddr("admin".to_string());
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            funder.clone(),
+            1000,
+            None,
+            None,
+        )
+        .unwrap();
+        deposit_rewards(&mut deps, mock_env("eth", &[], 1), funder, 2000, None, None).unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(2_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+        // Paid rewards are released, the principal and unbonding tokens are not
+        let redeem_msg = HandleMsg::Redeem {
+            amount: Some(Uint128(1_000_000_000_000)),
+            recipient: None,
+        };
+        handle(&mut deps, mock_env("user", &[], 501), redeem_msg).unwrap();
+        assert_eq!(reserved(&deps, 501), vec![500, 2_000_000_001_000]);
+
+        // Rewards that were distributed stay reserved after the schedule ends
+        let claim_msg = HandleMsg::ClaimRewards { recipient: None };
+        handle(&mut deps, mock_env("other", &[], 1001), claim_msg).unwrap();
+        assert_eq!(reserved(&deps, 1001), vec![500, 2_000_000_001_000]);
+
+        handle(
+            &mut deps,
+            mock_env("user", &[], 1001),
+            HandleMsg::ClaimUnbonded { recipient: None },
+        )
+        .unwrap();
+        assert_eq!(reserved(&deps, 1001), vec![500, 1_000_000_001_000]);
+
+        let claim_msg = HandleMsg::ClaimRewards { recipient: None };
+        handle(&mut deps, mock_env("user", &[], 1001), claim_msg).unwrap();
+        assert_eq!(reserved(&deps, 1001), vec![0, 1_000_000_000_000]);
+    }
+
+    #[test]
+    fn test_contract_status() {
+        let (init_result, mut deps) = init_helper(10000000);
+
+        let status_msg = HandleMsg::SetContractStatus {
+            status: ContractStatus::EmergencyOnly,
+        };
+        let handle_response = handle(
+            &mut deps,
+            mock_env("not_admin", &[], 10),
+            status_msg.clone(),
+        );
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::MissingRole {
+                address: HumanAddr("not_admin".to_string()),
+                role: Role::Pauser
+            })
+        );
+
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 10), status_msg);
+        let unwrapped_result: HandleAnswer =
+            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&HandleAnswer::SetContractStatus { status: Success }).unwrap()
+        );
+
+        let redeem_msg = HandleMsg::Redeem {
+            amount: None,
+            recipient: None,
+        };
+        let handle_response = handle(&mut deps, mock_env("user", &[], 20), redeem_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::GenericErr {
+                msg: r#"{"code":"contract_stopped","msg":"this action is not allowed while the contract status is EmergencyOnly"}"#
+                    .to_string(),
+                backtrace: None
+            }
+        );
+        set_vks(&mut deps, vec![HumanAddr("user".to_string())]);
+
+        let status_msg = HandleMsg::SetContractStatus {
+            status: ContractStatus::DepositsPaused,
+        };
+        handle(&mut deps, mock_env("admin", &[], 21), status_msg).unwrap();
+
+        let deposit_msg = HandleMsg::Receive {
+            sender: HumanAddr("user".to_string()),
+            from: HumanAddr("user".to_string()),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        let handle_response = handle(&mut deps, mock_env("eth", &[], 21), deposit_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::ContractStopped {
+                status: ContractStatus::DepositsPaused
+            })
+        );
+
+        let redeem_msg = HandleMsg::Redeem {
+            amount: None,
+            recipient: None,
+        };
+        let handle_response = handle(&mut deps, mock_env("user", &[], 21), redeem_msg);
+        let unwrapped_result: HandleAnswer =
+            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&HandleAnswer::Redeem { status: Success }).unwrap()
+        );
+
+        let result: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::ContractStatus {}).unwrap()).unwrap();
+        match result {
+            QueryAnswer::ContractStatus { status } => {
+                assert_eq!(status, ContractStatus::DepositsPaused)
+            }
+            _ => panic!("NOPE"),
+        }
+    }
+
+    #[test]
+    fn test_rewards_paused() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+        let status_msg = HandleMsg::SetContractStatus {
+            status: ContractStatus::RewardsPaused,
+        };
+        handle(&mut deps, mock_env("admin", &[], 251), status_msg).unwrap();
+
+        set_vks(&mut deps, vec![user.clone()]);
+        assert_eq!(query_rewards(&deps, user.clone(), 501), vec![25_000_000]);
+
+        // The rewards that weren't distributed while paused are spread over the rest of the schedule
+        let status_msg = HandleMsg::SetContractStatus {
+            status: ContractStatus::Normal,
+        };
+        handle(&mut deps, mock_env("admin", &[], 501), status_msg).unwrap();
+        assert_eq!(query_rewards(&deps, user.clone(), 751), vec![62_500_000]);
+        assert_eq!(query_rewards(&deps, user, 1001), vec![100_000_000]);
+    }
+
+    #[test]
+    fn test_emergency_redeem() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let users = vec![HumanAddr("alice".to_string()), HumanAddr("bob".to_string())];
+        for user in &users {
+            let deposit_msg = HandleMsg::Receive {
+                sender: user.clone(),
+                from: user.clone(),
+                amount: Uint128(1_000_000_000_000_000_000),
+                msg: to_binary(&ReceiveMsg::Deposit {
+                    lock_blocks: None,
+                    beneficiary: None,
+                })
+                .unwrap(),
+            };
+            handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+        }
+
+        let status_msg = HandleMsg::SetContractStatus {
+            status: ContractStatus::EmergencyOnly,
+        };
+        handle(&mut deps, mock_env("admin", &[], 251), status_msg).unwrap();
+
+        // Only the principal is paid back, the rewards are kept
+        for user in &users {
+            let handle_response = handle(
+                &mut deps,
+                mock_env(user.0.clone(), &[], 300),
+                HandleMsg::EmergencyRedeem { recipient: None },
+            )
+            .unwrap();
+            assert_eq!(
+                extract_transfer(&handle_response, "eth"),
+                1_000_000_000_000_000_000
+            );
+            assert_eq!(extract_transfer(&handle_response, "scrt"), 0);
+        }
+        set_vks(&mut deps, users.clone());
+        assert_eq!(
+            query_rewards(&deps, users[0].clone(), 300),
+            vec![12_500_000]
+        );
+
+        let claim_msg = HandleMsg::ClaimRewards { recipient: None };
+        let handle_response = handle(
+            &mut deps,
+            mock_env(users[0].0.clone(), &[], 300),
+            claim_msg.clone(),
+        );
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::ContractStopped {
+                status: ContractStatus::EmergencyOnly
+            })
+        );
+
+        let pay_msg = HandleMsg::PayFrozenRewards {
+            address: users[1].clone(),
+        };
+        let handle_response = handle(&mut deps, mock_env("user", &[], 300), pay_msg.clone());
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::MissingRole {
+                address: HumanAddr("user".to_string()),
+                role: Role::Treasury
+            })
+        );
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 300), pay_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 12_500_000);
+        assert_eq!(query_rewards(&deps, users[1].clone(), 300), vec![0]);
+
+        let status_msg = HandleMsg::SetContractStatus {
+            status: ContractStatus::Normal,
+        };
+        handle(&mut deps, mock_env("admin", &[], 400), status_msg).unwrap();
+        let handle_response =
+            handle(&mut deps, mock_env(users[0].0.clone(), &[], 400), claim_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 12_500_000);
+        assert_eq!(query_rewards(&deps, users[0].clone(), 400), vec![0]);
+    }
+
+    #[test]
+    fn test_admin() {
+        let (init_result, mut deps) = init_helper(10000000);
+
+        let propose_msg = HandleMsg::ProposeAdmin {
+            address: HumanAddr("not_admin".to_string()),
+        };
+        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 1), propose_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::Unauthorized {
+                address: HumanAddr("not_admin".to_string())
+            })
+        );
+
+        let propose_msg = HandleMsg::ProposeAdmin {
+            address: HumanAddr("new_admin".to_string()),
+        };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 1), propose_msg);
+        let unwrapped_result: HandleAnswer =
+            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&HandleAnswer::ProposeAdmin { status: Success }).unwrap()
+        );
+
+        let result: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::PendingAdmin {}).unwrap()).unwrap();
+        match result {
+            QueryAnswer::PendingAdmin { address } => {
+                assert_eq!(address, Some(HumanAddr("new_admin".to_string())))
+            }
+            _ => panic!("NOPE"),
+        }
+
+        // The admin doesn't change until the proposal is accepted
+        let accept_msg = HandleMsg::AcceptAdmin {};
+        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 1), accept_msg.clone());
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::NotPendingAdmin {
+                address: HumanAddr("not_admin".to_string())
+            })
+        );
+        let cancel_msg = HandleMsg::CancelAdminProposal {};
+        let handle_response = handle(&mut deps, mock_env("new_admin", &[], 1), cancel_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::Unauthorized {
+                address: HumanAddr("new_admin".to_string())
+            })
+        );
+
+        let handle_response = handle(&mut deps, mock_env("new_admin", &[], 1), accept_msg.clone());
+        let unwrapped_result: HandleAnswer =
+            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&HandleAnswer::AcceptAdmin { status: Success }).unwrap()
+        );
+
+        // The old admin's roles moved to the new admin
+        for role in Role::ALL.iter() {
+            assert_eq!(
+                load_role_members(&deps.storage, *role),
+                vec![HumanAddr("new_admin".to_string())]
+            );
+        }
+        let status_msg = HandleMsg::SetContractStatus {
+            status: ContractStatus::EmergencyOnly,
+        };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 1), status_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::MissingRole {
+                address: HumanAddr("admin".to_string()),
+                role: Role::Pauser
+            })
+        );
+
+        let propose_msg = HandleMsg::ProposeAdmin {
+            address: HumanAddr("not_admin".to_string()),
+        };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 1), propose_msg.clone());
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::Unauthorized {
+                address: HumanAddr("admin".to_string())
+            })
+        );
+
+        // A cancelled proposal can't be accepted
+        handle(&mut deps, mock_env("new_admin", &[], 1), propose_msg).unwrap();
+        let cancel_msg = HandleMsg::CancelAdminProposal {};
+        handle(&mut deps, mock_env("new_admin", &[], 1), cancel_msg).unwrap();
+        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 1), accept_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::NotPendingAdmin {
+                address: HumanAddr("not_admin".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn test_roles() {
+        let (init_result, mut deps) = init_helper(10000000);
+        assert!(init_result.is_ok());
+
+        let grant_msg = HandleMsg::GrantRole {
+            role: Role::Pauser,
+            address: HumanAddr("pauser".to_string()),
+        };
+        let handle_response = handle(&mut deps, mock_env("pauser", &[], 1), grant_msg.clone());
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::MissingRole {
+                address: HumanAddr("pauser".to_string()),
+                role: Role::RoleAdmin
+            })
+        );
+        handle(&mut deps, mock_env("admin", &[], 1), grant_msg).unwrap();
+
+        // The admin gives up the treasury role, but can still manage roles
+        let revoke_msg = HandleMsg::RevokeRole {
+            role: Role::Treasury,
+            address: HumanAddr("admin".to_string()),
         };
+        handle(&mut deps, mock_env("admin", &[], 1), revoke_msg).unwrap();
+        let revoke_msg = HandleMsg::RevokeRole {
+            role: Role::RoleAdmin,
+            address: HumanAddr("admin".to_string()),
+        };
+        handle(&mut deps, mock_env("admin", &[], 1), revoke_msg).unwrap();
 
-        (init(&mut deps, env, init_msg), deps)
-    }
+        let claim_msg = HandleMsg::ClaimRewardPool { to: None };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 10000001), claim_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::MissingRole {
+                address: HumanAddr("admin".to_string()),
+                role: Role::Treasury
+            })
+        );
 
-    /// Just set sender and sent funds for the message. The rest uses defaults.
-    /// The sender will be canonicalized internally to allow developers pasing in human readable senders.
-    /// This is intended for use in test code only.
-    pub fn mock_env<U: Into<HumanAddr>>(sender: U, sent: &[Coin], height: u64) -> Env {
-        Env {
-            block: BlockInfo {
-                height,
-                time: 1_571_797_419,
-                chain_id: "cosmos-testnet-14002".to_string(),
-            },
-            message: MessageInfo {
-                sender: sender.into(),
-                sent_funds: sent.to_vec(),
-            },
-            contract: ContractInfo {
-                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
-            },
-            contract_key: Some("".to_string()),
-            contract_code_hash: "".to_string(),
+        let status_msg = HandleMsg::SetContractStatus {
+            status: ContractStatus::EmergencyOnly,
+        };
+        handle(&mut deps, mock_env("pauser", &[], 2), status_msg).unwrap();
+
+        let result: QueryAnswer = from_binary(&query(&deps, QueryMsg::Roles {}).unwrap()).unwrap();
+        match result {
+            QueryAnswer::Roles { roles } => assert_eq!(
+                roles,
+                vec![
+                    RoleInfo {
+                        role: Role::Pauser,
+                        addresses: vec![
+                            HumanAddr("admin".to_string()),
+                            HumanAddr("pauser".to_string())
+                        ]
+                    },
+                    RoleInfo {
+                        role: Role::CampaignManager,
+                        addresses: vec![HumanAddr("admin".to_string())]
+                    },
+                    RoleInfo {
+                        role: Role::Treasury,
+                        addresses: vec![]
+                    },
+                    RoleInfo {
+                        role: Role::RoleAdmin,
+                        addresses: vec![]
+                    },
+                ]
+            ),
+            _ => panic!("NOPE"),
         }
     }
 
-    fn msg_from_action<S: Storage, A: Api, Q: Querier>(
-        deps: &Extern<S, A, Q>,
-        action: &str,
-        user: HumanAddr,
-    ) -> (HandleMsg, String) {
+    #[test]
+    fn test_deposit_redeem_round_trip() {
         let mut rng = rand::thread_rng();
-        let chance = rng.gen_range(0, 100000);
+        let (init_result, mut deps) = init_helper(10000000);
+        assert!(init_result.is_ok());
+
+        let user = HumanAddr("user".to_string());
+        let mut deposited: u128 = 0;
+        for block in 2..12 {
+            let amount: u128 = rng.gen_range(1, 1000e18 as u128);
+            deposited += amount;
+
+            let deposit_msg = HandleMsg::Receive {
+                sender: user.clone(),
+                from: user.clone(),
+                amount: Uint128(amount),
+                msg: to_binary(&ReceiveMsg::Deposit {
+                    lock_blocks: None,
+                    beneficiary: None,
+                })
+                .unwrap(),
+            };
+            handle(&mut deps, mock_env("eth", &[], block), deposit_msg).unwrap();
+        }
+
+        let redeem_msg = HandleMsg::Redeem {
+            amount: Some(Uint128(rng.gen_range(0, deposited))),
+            recipient: None,
+        };
+        let handle_response = handle(&mut deps, mock_env(user.0.clone(), &[], 12), redeem_msg);
+        let mut redeemed = extract_transfer(&handle_response.unwrap(), "eth");
+
+        let redeem_msg = HandleMsg::Redeem {
+            amount: None,
+            recipient: None,
+        };
+        let handle_response = handle(&mut deps, mock_env(user.0, &[], 13), redeem_msg);
+        redeemed += extract_transfer(&handle_response.unwrap(), "eth");
+
+        assert_eq!(redeemed, deposited);
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.inc_token_supply, 0);
+    }
+
+    #[test]
+    fn test_inc_token_scale() {
+        // A 6 decimals token, which would round down to no shares at all with a scale of 10^12
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            inc_token_scale: Uint128(1),
+            ..init_msg(1001, vec!["scrt"])
+        };
+        init(&mut deps, mock_env("admin", &[], 1), msg).unwrap();
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let alice = HumanAddr("alice".to_string());
+        let bob = HumanAddr("bob".to_string());
+        for (user, amount) in &[(&alice, 1_500_000), (&bob, 500_000)] {
+            let deposit_msg = HandleMsg::Receive {
+                sender: (*user).clone(),
+                from: (*user).clone(),
+                amount: Uint128(*amount),
+                msg: to_binary(&ReceiveMsg::Deposit {
+                    lock_blocks: None,
+                    beneficiary: None,
+                })
+                .unwrap(),
+            };
+            handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+            let user_info: UserInfo = TypedStore::attach(&deps.storage)
+                .load(user.0.as_bytes())
+                .unwrap();
+            assert_eq!(user_info.shares, *amount);
+        }
+
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.inc_token_supply, 2_000_000);
+
+        set_vks(&mut deps, vec![alice.clone(), bob.clone()]);
+        assert_eq!(query_rewards(&deps, alice.clone(), 501), vec![37_500_000]);
+        assert_eq!(query_rewards(&deps, bob.clone(), 501), vec![12_500_000]);
+
+        let redeem_msg = Redeem {
+            amount: None,
+            recipient: None,
+        };
+        let handle_response = handle(&mut deps, mock_env(alice.0.clone(), &[], 1001), redeem_msg);
+        assert_eq!(extract_rewards(handle_response), 75_000_000);
+        assert_eq!(query_rewards(&deps, bob, 1001), vec![25_000_000]);
+    }
+
+    #[test]
+    fn test_deposit_for_beneficiary() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let treasury = HumanAddr("treasury".to_string());
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = |lock_blocks| HandleMsg::Receive {
+            sender: treasury.clone(),
+            from: treasury.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks,
+                beneficiary: Some(user.clone()),
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg(None)).unwrap();
+
+        // Topping up pays the pending rewards to the beneficiary
+        let handle_response =
+            handle(&mut deps, mock_env("eth", &[], 501), deposit_msg(None)).unwrap();
+        assert!(!handle_response
+            .log
+            .iter()
+            .any(|attribute| attribute.key == "reward"));
+        match &handle_response.messages[0] {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+                assert_eq!(
+                    from_binary::<Snip20HandleMsg>(msg).unwrap(),
+                    Snip20HandleMsg::Transfer {
+                        recipient: user.clone(),
+                        amount: Uint128(50_000_000),
+                        padding: None,
+                    }
+                );
+            }
+            _ => panic!("NOPE"),
+        }
+
+        let handle_response = handle(&mut deps, mock_env("eth", &[], 502), deposit_msg(Some(10)));
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::LockupExtensionNotAllowed {
+                address: user.clone()
+            })
+        );
+
+        // A third party can't lock an empty position, which would hold the beneficiary's own deposits
+        let victim = HumanAddr("victim".to_string());
+        let griefing_msg = HandleMsg::Receive {
+            sender: treasury.clone(),
+            from: treasury.clone(),
+            amount: Uint128(1),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: Some(1000),
+                beneficiary: Some(victim.clone()),
+            })
+            .unwrap(),
+        };
+        let handle_response = handle(&mut deps, mock_env("eth", &[], 502), griefing_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::LockupExtensionNotAllowed {
+                address: victim.clone()
+            })
+        );
+
+        let gift_msg = HandleMsg::Receive {
+            sender: treasury.clone(),
+            from: treasury.clone(),
+            amount: Uint128(1),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: Some(victim.clone()),
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 502), gift_msg).unwrap();
+        let self_deposit_msg = HandleMsg::Receive {
+            sender: victim.clone(),
+            from: victim.clone(),
+            amount: Uint128(1_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 503), self_deposit_msg).unwrap();
+        let redeem_msg = Redeem {
+            amount: None,
+            recipient: None,
+        };
+        let handle_response =
+            handle(&mut deps, mock_env(victim.0.clone(), &[], 504), redeem_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "eth"), 1_000_000_000_001);
+
+        set_vks(&mut deps, vec![user.clone(), treasury.clone()]);
+        let query_deposit = |address| {
+            let query_msg = QueryMsg::Deposit {
+                address,
+                key: "42".to_string(),
+            };
+            match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+                QueryAnswer::Deposit { deposit, .. } => deposit.u128(),
+                _ => panic!("NOPE"),
+            }
+        };
+        assert_eq!(query_deposit(user), 2_000_000_000_000_000_000);
+        assert_eq!(query_deposit(treasury), 0);
+    }
+
+    #[test]
+    fn test_lockup_boost() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            300_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let locker = HumanAddr("locker".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: locker.clone(),
+            from: locker.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: Some(1000),
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+        set_vks(&mut deps, vec![locker.clone(), user.clone()]);
+        assert_eq!(
+            query_rewards(&deps, locker.clone(), 1001),
+            vec![200_000_000]
+        );
+        assert_eq!(query_rewards(&deps, user.clone(), 1001), vec![100_000_000]);
+
+        let redeem_msg = HandleMsg::Redeem {
+            amount: None,
+            recipient: None,
+        };
+        let handle_response = handle(
+            &mut deps,
+            mock_env(locker.0.clone(), &[], 500),
+            redeem_msg.clone(),
+        );
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::TokensLocked {
+                unlock_height: 1001
+            })
+        );
+
+        let handle_response = handle(&mut deps, mock_env(locker.0, &[], 1001), redeem_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 200_000_000);
+        assert_eq!(
+            extract_transfer(&handle_response, "eth"),
+            1_000_000_000_000_000_000
+        );
+    }
+
+    #[test]
+    fn test_kick() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            300_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let locker = HumanAddr("locker".to_string());
+        let user = HumanAddr("user".to_string());
+        for (address, lock_blocks) in &[(&locker, Some(500)), (&user, None)] {
+            let deposit_msg = HandleMsg::Receive {
+                sender: (*address).clone(),
+                from: (*address).clone(),
+                amount: Uint128(1_000_000_000_000_000_000),
+                msg: to_binary(&ReceiveMsg::Deposit {
+                    lock_blocks: *lock_blocks,
+                    beneficiary: None,
+                })
+                .unwrap(),
+            };
+            handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+        }
+
+        let kick_msg = HandleMsg::Kick {
+            address: locker.clone(),
+        };
+        let handle_response = handle(&mut deps, mock_env("anyone", &[], 500), kick_msg.clone());
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::NothingToKick {
+                address: locker.clone()
+            })
+        );
+
+        // The locker earned 1.5x until unlocking, and 1x from the kick on
+        let handle_response = handle(&mut deps, mock_env("anyone", &[], 501), kick_msg).unwrap();
+        assert!(handle_response.messages.is_empty());
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.inc_token_supply, 2_000_000);
+
+        set_vks(&mut deps, vec![locker.clone(), user.clone()]);
+        assert_eq!(query_rewards(&deps, locker, 1001), vec![165_000_000]);
+        assert_eq!(query_rewards(&deps, user, 1001), vec![135_000_000]);
+    }
+
+    #[test]
+    fn test_unbonding() {
+        let (init_result, mut deps) = init_helper(10000000);
+        assert!(init_result.is_ok());
+
+        let mut config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
+        config.unbonding_period = 100;
+        TypedStoreMut::attach(&mut deps.storage)
+            .store(CONFIG_KEY, &config)
+            .unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+        let redeem_msg = HandleMsg::Redeem {
+            amount: None,
+            recipient: None,
+        };
+        let handle_response =
+            handle(&mut deps, mock_env(user.0.clone(), &[], 10), redeem_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "eth"), 0);
+
+        set_vks(&mut deps, vec![user.clone()]);
+        let query_msg = QueryMsg::Unbondings {
+            address: user.clone(),
+            key: "42".to_string(),
+        };
+        let result: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match result {
+            QueryAnswer::Unbondings { unbondings } => assert_eq!(
+                unbondings,
+                vec![UnbondingInfo {
+                    amount: Uint128(1_000_000_000_000_000_000),
+                    release_height: 110,
+                }]
+            ),
+            _ => panic!("NOPE"),
+        }
+
+        let claim_msg = HandleMsg::ClaimUnbonded { recipient: None };
+        let handle_response = handle(
+            &mut deps,
+            mock_env(user.0.clone(), &[], 109),
+            claim_msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(extract_transfer(&handle_response, "eth"), 0);
+
+        let handle_response = handle(&mut deps, mock_env(user.0, &[], 110), claim_msg).unwrap();
+        assert_eq!(
+            extract_transfer(&handle_response, "eth"),
+            1_000_000_000_000_000_000
+        );
+    }
+
+    #[test]
+    fn test_claim_rewards() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+        let claim_msg = HandleMsg::ClaimRewards {
+            recipient: Some(HumanAddr("cold".to_string())),
+        };
+        let handle_response =
+            handle(&mut deps, mock_env(user.0.clone(), &[], 501), claim_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 50_000_000);
+        assert_eq!(extract_transfer(&handle_response, "eth"), 0);
+        assert_eq!(
+            handle_response.log,
+            vec![
+                log("action", "claim_rewards"),
+                log("reward", "scrt:50000000")
+            ]
+        );
+        match &handle_response.messages[0] {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+                match from_binary::<Snip20HandleMsg>(msg).unwrap() {
+                    Snip20HandleMsg::Transfer { recipient, .. } => {
+                        assert_eq!(recipient, HumanAddr("cold".to_string()))
+                    }
+                }
+            }
+            _ => panic!("NOPE"),
+        }
+
+        set_vks(&mut deps, vec![user.clone()]);
+        assert_eq!(query_rewards(&deps, user.clone(), 1001), vec![50_000_000]);
+
+        let query_msg = QueryMsg::Rewards {
+            address: user,
+            height: 500,
+            key: "42".to_string(),
+        };
+        assert_eq!(
+            query(&deps, query_msg).unwrap_err(),
+            StdError::from(ContractError::HeightTooOld {
+                height: 500,
+                last_reward_block: 501
+            })
+        );
+    }
+
+    #[test]
+    fn test_rewards_query_lookahead() {
+        let (init_result, mut deps) = init_helper(1_000_001);
+        assert!(init_result.is_ok());
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let alice = HumanAddr("alice".to_string());
+        let bob = HumanAddr("bob".to_string());
+        for user in &[&alice, &bob] {
+            let deposit_msg = HandleMsg::Receive {
+                sender: (*user).clone(),
+                from: (*user).clone(),
+                amount: Uint128(1_000_000_000_000_000_000),
+                msg: to_binary(&ReceiveMsg::Deposit {
+                    lock_blocks: None,
+                    beneficiary: None,
+                })
+                .unwrap(),
+            };
+            handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+        }
+        set_vks(&mut deps, vec![alice.clone(), bob.clone()]);
+
+        // While a schedule is running, queries can't look too far past the last update
+        assert_eq!(
+            query_rewards(&deps, alice.clone(), 1 + MAX_REWARDS_QUERY_LOOKAHEAD),
+            vec![25_000_000]
+        );
+        let query_msg = QueryMsg::Rewards {
+            address: alice.clone(),
+            height: 2 + MAX_REWARDS_QUERY_LOOKAHEAD,
+            key: "42".to_string(),
+        };
+        assert_eq!(
+            query(&deps, query_msg).unwrap_err(),
+            StdError::from(ContractError::HeightTooFarAhead {
+                height: 2 + MAX_REWARDS_QUERY_LOOKAHEAD,
+                last_reward_block: 1,
+            })
+        );
+
+        // Once every schedule ended there's nothing left to accrue, so any height is fine
+        let claim_msg = HandleMsg::ClaimRewards { recipient: None };
+        let handle_response = handle(
+            &mut deps,
+            mock_env(bob.0.clone(), &[], 1_000_001),
+            claim_msg,
+        )
+        .unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 50_000_000);
+        assert_eq!(
+            query_rewards(&deps, alice, 1_000_001 + 2 * MAX_REWARDS_QUERY_LOOKAHEAD),
+            vec![50_000_000]
+        );
+    }
+
+    #[test]
+    fn test_redeem_to_recipient() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+        let redeem_msg = HandleMsg::Redeem {
+            amount: None,
+            recipient: Some(HumanAddr("".to_string())),
+        };
+        let handle_response = handle(&mut deps, mock_env(user.0.clone(), &[], 501), redeem_msg);
+        assert!(handle_response.is_err());
+
+        let redeem_msg = HandleMsg::Redeem {
+            amount: None,
+            recipient: Some(HumanAddr("cold".to_string())),
+        };
+        let handle_response = handle(&mut deps, mock_env(user.0, &[], 501), redeem_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 50_000_000);
+        assert_eq!(
+            extract_transfer(&handle_response, "eth"),
+            1_000_000_000_000_000_000
+        );
+        for message in &handle_response.messages {
+            match message {
+                CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+                    match from_binary::<Snip20HandleMsg>(msg).unwrap() {
+                        Snip20HandleMsg::Transfer { recipient, .. } => {
+                            assert_eq!(recipient, HumanAddr("cold".to_string()))
+                        }
+                    }
+                }
+                _ => panic!("NOPE"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_transfer_position() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let alice = HumanAddr("alice".to_string());
+        let bob = HumanAddr("bob".to_string());
+        let carol = HumanAddr("carol".to_string());
+        for (user, lock_blocks) in &[(&alice, None), (&bob, None), (&carol, Some(1000))] {
+            let deposit_msg = HandleMsg::Receive {
+                sender: (*user).clone(),
+                from: (*user).clone(),
+                amount: Uint128(1_000_000_000_000_000_000),
+                msg: to_binary(&ReceiveMsg::Deposit {
+                    lock_blocks: *lock_blocks,
+                    beneficiary: None,
+                })
+                .unwrap(),
+            };
+            handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+        }
+        let supply = TypedStore::<RewardPool, _>::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap()
+            .inc_token_supply;
+
+        let transfer_msg = HandleMsg::TransferPosition {
+            recipient: alice.clone(),
+            amount: None,
+        };
+        let handle_response = handle(&mut deps, mock_env(alice.0.clone(), &[], 501), transfer_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::TransferToSelf {})
+        );
+
+        // Carol's tokens are still locked, and moving them would lock Dave's later deposits
+        let dave = HumanAddr("dave".to_string());
+        let transfer_msg = HandleMsg::TransferPosition {
+            recipient: dave.clone(),
+            amount: Some(Uint128(1)),
+        };
+        let handle_response = handle(&mut deps, mock_env(carol.0.clone(), &[], 501), transfer_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::TokensLocked {
+                unlock_height: 1001
+            })
+        );
+
+        let transfer_msg = HandleMsg::TransferPosition {
+            recipient: bob.clone(),
+            amount: Some(Uint128(500_000_000_000_000_000)),
+        };
+        let handle_response =
+            handle(&mut deps, mock_env(alice.0.clone(), &[], 501), transfer_msg).unwrap();
+        let rewards: Vec<(HumanAddr, u128)> = handle_response
+            .messages
+            .iter()
+            .map(|message| match message {
+                CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+                    match from_binary::<Snip20HandleMsg>(msg).unwrap() {
+                        Snip20HandleMsg::Transfer {
+                            recipient, amount, ..
+                        } => (recipient, amount.u128()),
+                    }
+                }
+                _ => panic!("NOPE"),
+            })
+            .collect();
+        assert_eq!(
+            rewards,
+            vec![(alice.clone(), 12_500_000), (bob.clone(), 12_500_000)]
+        );
 
-        match action {
-            "deposit" => {
-                let amount: u128 = rng.gen_range(10e12 as u128, 1000e18 as u128);
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.inc_token_supply, supply);
+        assert_eq!(reward_pool.inc_token_locked, 3_000_000_000_000_000_000);
 
-                let msg = HandleMsg::Receive {
-                    sender: user.clone(),
-                    from: user,
-                    amount: Uint128(amount),
-                    msg: to_binary(&ReceiveMsg::Deposit {}).unwrap(),
-                };
+        set_vks(&mut deps, vec![alice.clone(), bob.clone()]);
+        assert_eq!(query_rewards(&deps, alice, 1001), vec![6_250_000]);
+        assert_eq!(query_rewards(&deps, bob.clone(), 1001), vec![18_750_000]);
 
-                (msg, "eth".to_string())
+        let query_msg = QueryMsg::Deposit {
+            address: bob,
+            key: "42".to_string(),
+        };
+        let result: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match result {
+            QueryAnswer::Deposit { deposit, .. } => {
+                assert_eq!(deposit, Uint128(1_500_000_000_000_000_000))
             }
-            "redeem" => {
-                let amount: u128 = rng.gen_range(1e12 as u128, 1000e18 as u128);
+            _ => panic!("NOPE"),
+        }
 
-                let msg = HandleMsg::Redeem {
-                    amount: Some(Uint128(amount)),
-                };
+        // Once unlocked, the tokens move without the lockup or its boost
+        let transfer_msg = HandleMsg::TransferPosition {
+            recipient: dave.clone(),
+            amount: None,
+        };
+        handle(&mut deps, mock_env(carol.0, &[], 1001), transfer_msg).unwrap();
+        let dave_info: UserInfo = TypedStore::attach(&deps.storage)
+            .load(dave.0.as_bytes())
+            .unwrap();
+        assert_eq!(dave_info.locked, 1_000_000_000_000_000_000);
+        assert_eq!(dave_info.unlock_height, 0);
+        assert_eq!(dave_info.boost, BOOST_SCALE);
+        assert_eq!(dave_info.shares, 1_000_000);
 
-                (msg, user.0)
-            }
-            "deadline" if chance == 42 => {
-                let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
-                let current = config.deadline as f64;
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.inc_token_supply, 3_000_000);
+    }
 
-                let new = rng.gen_range(current + 1.0, current * 1.001);
+    #[test]
+    fn test_receipt_token() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
 
-                let msg = HandleMsg::SetDeadline { block: new as u64 };
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let alice = HumanAddr("alice".to_string());
+        let bob = HumanAddr("bob".to_string());
+        let carol = HumanAddr("carol".to_string());
+        let dave = HumanAddr("dave".to_string());
+        for user in &[&alice, &bob] {
+            let deposit_msg = HandleMsg::Receive {
+                sender: (*user).clone(),
+                from: (*user).clone(),
+                amount: Uint128(1_000_000_000_000_000_000),
+                msg: to_binary(&ReceiveMsg::Deposit {
+                    lock_blocks: None,
+                    beneficiary: None,
+                })
+                .unwrap(),
+            };
+            handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+        }
 
-                (msg, "admin".to_string())
-            }
-            "rewards" if chance == 7 => {
-                let amount: u128 = rng.gen_range(10000e6 as u128, 100000e6 as u128);
+        let transfer_msg = HandleMsg::Transfer {
+            recipient: bob.clone(),
+            amount: Uint128(500_000_000_000_000_000),
+            padding: None,
+        };
+        let handle_response =
+            handle(&mut deps, mock_env(alice.0.clone(), &[], 501), transfer_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 50_000_000);
+        assert_eq!(
+            handle_response.log,
+            vec![
+                log("action", "transfer"),
+                log("amount", 500_000_000_000_000_000u128),
+                log("recipient", &bob),
+                log("reward", "scrt:25000000"),
+            ]
+        );
 
-                let msg = HandleMsg::Receive {
-                    sender: user.clone(),
-                    from: user,
-                    amount: Uint128(amount),
-                    msg: to_binary(&ReceiveMsg::DepositRewards {}).unwrap(),
-                };
+        let allowance_msg = HandleMsg::IncreaseAllowance {
+            spender: carol.clone(),
+            amount: Uint128(200_000_000_000_000_000),
+            expiration: None,
+            padding: None,
+        };
+        handle(
+            &mut deps,
+            mock_env(alice.0.clone(), &[], 502),
+            allowance_msg,
+        )
+        .unwrap();
+
+        let transfer_msg = HandleMsg::TransferFrom {
+            owner: alice.clone(),
+            recipient: dave.clone(),
+            amount: Uint128(300_000_000_000_000_000),
+            padding: None,
+        };
+        let handle_response = handle(&mut deps, mock_env(carol.0.clone(), &[], 502), transfer_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::InsufficientAllowance {
+                allowance: 200_000_000_000_000_000,
+                required: 300_000_000_000_000_000,
+            })
+        );
 
-                (msg, "scrt".to_string())
+        let register_msg = HandleMsg::RegisterReceive {
+            code_hash: "4".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env(dave.0.clone(), &[], 502), register_msg).unwrap();
+
+        let send_msg = HandleMsg::SendFrom {
+            owner: alice.clone(),
+            recipient: dave.clone(),
+            recipient_code_hash: None,
+            amount: Uint128(200_000_000_000_000_000),
+            msg: Some(Binary::from(b"hi".to_vec())),
+            padding: None,
+        };
+        let handle_response =
+            handle(&mut deps, mock_env(carol.0.clone(), &[], 502), send_msg).unwrap();
+        assert_eq!(
+            handle_response.messages.last().unwrap(),
+            &CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: dave.clone(),
+                callback_code_hash: "4".to_string(),
+                msg: to_binary(&ReceiverHandleMsg::Receive {
+                    sender: carol.clone(),
+                    from: alice.clone(),
+                    amount: Uint128(200_000_000_000_000_000),
+                    msg: Some(Binary::from(b"hi".to_vec())),
+                })
+                .unwrap(),
+                send: vec![],
+            })
+        );
+        // The spender doesn't learn the owner's rewards
+        assert_eq!(
+            handle_response.log,
+            vec![
+                log("action", "send_from"),
+                log("amount", 200_000_000_000_000_000u128),
+                log("recipient", &dave),
+                log("owner", &alice),
+            ]
+        );
+
+        set_vks(&mut deps, vec![alice.clone(), bob.clone(), carol.clone()]);
+        let balance = |address| {
+            let query_msg = QueryMsg::Balance {
+                address,
+                key: "42".to_string(),
+            };
+            match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+                QueryAnswer::Balance { amount } => amount.u128(),
+                _ => panic!("NOPE"),
             }
-            _ => (
-                HandleMsg::Redeem {
-                    amount: Some(Uint128(u128::MAX)), // This will never work but will keep the tests going
-                },
-                "".to_string(),
-            ),
+        };
+        assert_eq!(balance(alice.clone()), 300_000_000_000_000_000);
+        assert_eq!(balance(bob), 1_500_000_000_000_000_000);
+
+        let query_msg = QueryMsg::Allowance {
+            owner: alice,
+            spender: carol,
+            key: "42".to_string(),
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::Allowance { allowance, .. } => assert_eq!(allowance, Uint128(0)),
+            _ => panic!("NOPE"),
         }
     }
 
-    fn print_status(
-        deps: &Extern<MockStorage, MockApi, MockQuerier>,
-        users: Vec<HumanAddr>,
-        block: u64,
-    ) {
-        let reward_pool = TypedStore::<RewardPool, MockStorage>::attach(&deps.storage)
-            .load(REWARD_POOL_KEY)
-            .unwrap();
-        let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
-
-        println!("####### Statistics for block: {} #######", block);
-        println!("Deadline: {}", config.deadline);
-        println!("Locked ETH: {}", reward_pool.inc_token_supply);
-        println!("Pending rewards: {}", reward_pool.pending_rewards);
-        println!(
-            "Accumulated rewards per share: {}",
-            reward_pool.acc_reward_per_share
-        );
-        println!("Last reward block: {}", reward_pool.last_reward_block);
+    #[test]
+    fn test_receipt_token_locked() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        let alice = HumanAddr("alice".to_string());
+        let bob = HumanAddr("bob".to_string());
+        let dave = HumanAddr("dave".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: alice.clone(),
+            from: alice.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: Some(1000),
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
 
-        for user in users {
-            println!("## {}:", user.0);
-            let user_info = TypedStore::<UserInfo, MockStorage>::attach(&deps.storage)
-                .load(user.0.as_bytes())
-                .unwrap_or(UserInfo { locked: 0, debt: 0 });
-            let rewards = query_rewards(deps, user.clone(), block);
+        let allowance_msg = HandleMsg::IncreaseAllowance {
+            spender: bob.clone(),
+            amount: Uint128(1),
+            expiration: None,
+            padding: None,
+        };
+        handle(&mut deps, mock_env(alice.0.clone(), &[], 1), allowance_msg).unwrap();
 
-            println!("Locked: {}", user_info.locked);
-            println!("Debt: {}", user_info.debt);
-            println!("Reward: {}", rewards);
+        // Locked tokens can't be pushed onto anyone, whichever way they're transferred
+        let transfer_msg = HandleMsg::Transfer {
+            recipient: dave.clone(),
+            amount: Uint128(1),
+            padding: None,
+        };
+        let send_msg = HandleMsg::Send {
+            recipient: dave.clone(),
+            recipient_code_hash: None,
+            amount: Uint128(1),
+            msg: None,
+            padding: None,
+        };
+        let transfer_from_msg = HandleMsg::TransferFrom {
+            owner: alice.clone(),
+            recipient: dave.clone(),
+            amount: Uint128(1),
+            padding: None,
+        };
+        for (sender, msg) in vec![
+            (&alice, transfer_msg.clone()),
+            (&alice, send_msg),
+            (&bob, transfer_from_msg),
+        ] {
+            let handle_response = handle(&mut deps, mock_env(sender.0.clone(), &[], 501), msg);
+            assert_eq!(
+                handle_response.unwrap_err(),
+                StdError::from(ContractError::TokensLocked {
+                    unlock_height: 1001
+                })
+            );
         }
+        let dave_info: Option<UserInfo> = TypedStore::attach(&deps.storage)
+            .may_load(dave.0.as_bytes())
+            .unwrap();
+        assert!(dave_info.is_none());
 
-        println!();
+        handle(&mut deps, mock_env(alice.0, &[], 1001), transfer_msg).unwrap();
+        let dave_info: UserInfo = TypedStore::attach(&deps.storage)
+            .load(dave.0.as_bytes())
+            .unwrap();
+        assert_eq!(dave_info.locked, 1);
+        assert_eq!(dave_info.unlock_height, 0);
     }
 
-    fn query_rewards(
-        deps: &Extern<MockStorage, MockApi, MockQuerier>,
-        user: HumanAddr,
-        block: u64,
-    ) -> u128 {
-        let query_msg = QueryMsg::Rewards {
-            address: user,
-            height: block,
-            key: "42".to_string(),
+    #[test]
+    fn test_token_info() {
+        let (init_result, deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+        let mut deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: Snip20Querier { balance: 0 },
         };
 
-        let result: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user,
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+        let result: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::TokenInfo {}).unwrap()).unwrap();
         match result {
-            QueryAnswer::Rewards { rewards } => rewards.u128(),
+            QueryAnswer::TokenInfo {
+                name,
+                symbol,
+                decimals,
+                total_supply,
+            } => {
+                assert_eq!(name, "Locked Ether");
+                assert_eq!(symbol, "LETH");
+                assert_eq!(decimals, 18);
+                assert_eq!(total_supply, Some(Uint128(1_000_000_000_000_000_000)));
+            }
             _ => panic!("NOPE"),
         }
     }
 
-    fn set_vks(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, users: Vec<HumanAddr>) {
-        for user in users {
-            let vk_msg = SetViewingKey {
-                key: "42".to_string(),
-                padding: None,
+    #[test]
+    fn test_compound() {
+        let (init_result, mut deps) = init_helper_with_reward_tokens(1001, vec!["eth"]);
+        assert!(init_result.is_ok());
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("eth", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+        let handle_response = handle(
+            &mut deps,
+            mock_env(user.0.clone(), &[], 501),
+            HandleMsg::Compound {},
+        )
+        .unwrap();
+        assert!(handle_response.messages.is_empty());
+
+        let auto_compound_msg = HandleMsg::SetAutoCompound { enabled: true };
+        handle(
+            &mut deps,
+            mock_env(user.0.clone(), &[], 501),
+            auto_compound_msg,
+        )
+        .unwrap();
+
+        let compound_all_msg = HandleMsg::CompoundAll {
+            start: None,
+            limit: None,
+        };
+        handle(&mut deps, mock_env("anyone", &[], 1001), compound_all_msg).unwrap();
+
+        let user_info: UserInfo = TypedStore::attach(&deps.storage)
+            .load(user.0.as_bytes())
+            .unwrap();
+        assert_eq!(user_info.locked, 1_000_000_000_100_000_000);
+
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.inc_token_supply, user_info.shares);
+    }
+
+    #[test]
+    fn test_auto_compound_list() {
+        let (init_result, mut deps) = init_helper_with_reward_tokens(1001, vec!["eth"]);
+        assert!(init_result.is_ok());
+
+        deposit_rewards(
+            &mut deps,
+            mock_env("eth", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let auto_compound_msg = |enabled| HandleMsg::SetAutoCompound { enabled };
+        let handle_response = handle(
+            &mut deps,
+            mock_env("nobody", &[], 1),
+            auto_compound_msg(true),
+        );
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::NoDeposit {
+                address: HumanAddr("nobody".to_string())
+            })
+        );
+
+        let alice = HumanAddr("alice".to_string());
+        let bob = HumanAddr("bob".to_string());
+        let carol = HumanAddr("carol".to_string());
+        for user in &[&alice, &bob, &carol] {
+            let deposit_msg = HandleMsg::Receive {
+                sender: (*user).clone(),
+                from: (*user).clone(),
+                amount: Uint128(1_000_000_000_000_000_000),
+                msg: to_binary(&ReceiveMsg::Deposit {
+                    lock_blocks: None,
+                    beneficiary: None,
+                })
+                .unwrap(),
             };
-            handle(deps, mock_env(user.0, &[], 2001), vk_msg).unwrap();
+            handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+            handle(
+                &mut deps,
+                mock_env(user.0.clone(), &[], 1),
+                auto_compound_msg(true),
+            )
+            .unwrap();
+        }
+        assert_eq!(compounder_count(&deps.storage), 3);
+
+        // Opting out moves the last compounder into the freed index, and opting out twice changes nothing
+        for _ in 0..2 {
+            handle(
+                &mut deps,
+                mock_env(alice.0.clone(), &[], 1),
+                auto_compound_msg(false),
+            )
+            .unwrap();
+        }
+        assert_eq!(compounder_count(&deps.storage), 2);
+        assert_eq!(load_compounder(&deps.storage, 0).unwrap(), carol);
+        assert_eq!(load_compounder(&deps.storage, 1).unwrap(), bob);
+
+        let compound_all_msg = HandleMsg::CompoundAll {
+            start: Some(1),
+            limit: Some(1),
+        };
+        let handle_response =
+            handle(&mut deps, mock_env("anyone", &[], 301), compound_all_msg).unwrap();
+        assert_eq!(
+            handle_response.log,
+            vec![log("action", "compound_all"), log("compounded", 1)]
+        );
+        for (user, locked) in &[
+            (&alice, 1_000_000_000_000_000_000),
+            (&bob, 1_000_000_000_010_000_000),
+            (&carol, 1_000_000_000_000_000_000),
+        ] {
+            let user_info: UserInfo = TypedStore::attach(&deps.storage)
+                .load(user.0.as_bytes())
+                .unwrap();
+            assert_eq!(user_info.locked, *locked);
         }
     }
 
-    fn extract_rewards(result: StdResult<HandleResponse>) -> u128 {
-        match result {
-            Ok(resp) => {
-                for message in resp.messages {
-                    match message {
-                        CosmosMsg::Wasm(w) => match w {
-                            WasmMsg::Execute {
-                                contract_addr, msg, ..
-                            } => {
-                                if contract_addr == HumanAddr("scrt".to_string()) {
-                                    let transfer_msg: Snip20HandleMsg = from_binary(&msg).unwrap();
+    #[test]
+    fn test_compound_locked_position() {
+        let (init_result, mut deps) = init_helper_with_reward_tokens(1001, vec!["eth"]);
+        assert!(init_result.is_ok());
 
-                                    match transfer_msg {
-                                        Snip20HandleMsg::Transfer { amount, .. } => {
-                                            return amount.u128();
-                                        }
-                                        _ => panic!(),
-                                    }
-                                }
-                            }
-                            _ => panic!(),
-                        },
-                        _ => panic!(),
-                    }
-                }
-            }
-            Err(e) => match e {
-                StdError::NotFound { .. } => {}
-                StdError::GenericErr { msg, backtrace } => {
-                    if !msg.contains("insufficient") {
-                        panic!(format!("{}", msg))
-                    }
-                }
-                _ => panic!(format!("{:?}", e)),
+        deposit_rewards(
+            &mut deps,
+            mock_env("eth", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: Some(1000),
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+        // Half of the lockup is left, so the compounded rewards are boosted by 1.5x and not by the
+        // position's 2x
+        handle(
+            &mut deps,
+            mock_env(user.0.clone(), &[], 501),
+            HandleMsg::Compound {},
+        )
+        .unwrap();
+
+        let user_info: UserInfo = TypedStore::attach(&deps.storage)
+            .load(user.0.as_bytes())
+            .unwrap();
+        let compounded = user_info.locked - 1_000_000_000_000_000_000;
+        assert!(compounded > 0);
+        assert_eq!(user_info.unlock_height, 1001);
+        assert_eq!(
+            user_info.boost as u128,
+            (1_000_000_000_000_000_000 * 2 * BOOST_SCALE as u128
+                + compounded * 3 * BOOST_SCALE as u128 / 2)
+                / user_info.locked
+        );
+
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.inc_token_supply, user_info.shares);
+    }
+
+    #[test]
+    fn test_permit_for_another_contract() {
+        let (init_result, deps) = init_helper(10000000);
+        assert!(init_result.is_ok());
+
+        let query_msg = QueryMsg::WithPermit {
+            permit: Permit {
+                params: PermitParams {
+                    allowed_tokens: vec![HumanAddr("another_contract".to_string())],
+                    permit_name: "test".to_string(),
+                    chain_id: "cosmos-testnet-14002".to_string(),
+                    permissions: vec![Permission::Owner],
+                },
+                signature: PermitSignature {
+                    pub_key: PubKey {
+                        kind: "tendermint/PubKeySecp256k1".to_string(),
+                        value: Binary::from(vec![2u8; 33]),
+                    },
+                    signature: Binary::from(vec![0u8; 64]),
+                },
             },
-        }
+            query: QueryWithPermit::Deposit {},
+        };
+        assert_eq!(
+            query(&deps, query_msg).unwrap_err(),
+            StdError::from(ContractError::PermitNotForContract {
+                contract_address: HumanAddr::from(MOCK_CONTRACT_ADDR)
+            })
+        );
+    }
 
-        0
+    /// A permit for `MOCK_CONTRACT_ADDR` with the `deposit` permission, signed by a fixed key
+    fn signed_permit() -> Permit {
+        Permit {
+            params: PermitParams {
+                allowed_tokens: vec![HumanAddr::from(MOCK_CONTRACT_ADDR)],
+                permit_name: "test".to_string(),
+                chain_id: "secret-4".to_string(),
+                permissions: vec![Permission::Deposit],
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    kind: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64("AyA8gee2TUoMZT0UOcgUhNsW1HJ67p9ygDAPlQVFW6d8")
+                        .unwrap(),
+                },
+                signature: Binary::from_base64(
+                    "rwHgStCOiMMd2i/JS0T51rcAbKimMG+3G4VuGpTS7SB3KNHicgK/gtKCNTxfVWTPOdA7O0ywya2dVa7sUOSY3Q==",
+                )
+                .unwrap(),
+            },
+        }
     }
 
-    fn extract_reward_deposit(msg: HandleMsg) -> u128 {
-        match msg {
-            HandleMsg::Receive { amount, msg, .. } => {
-                let transfer_msg: ReceiveMsg = from_binary(&msg).unwrap();
+    #[test]
+    fn test_permit() {
+        let (init_result, mut deps) = init_helper(10000000);
+        assert!(init_result.is_ok());
+
+        // ripemd160(sha256(pub_key)) of the fixture key
+        let signer = deps
+            .api
+            .human_address(&CanonicalAddr(Binary(vec![
+                120, 67, 51, 27, 43, 106, 15, 27, 20, 54, 14, 91, 33, 8, 48, 102, 9, 7, 57, 11,
+            ])))
+            .unwrap();
 
-                match transfer_msg {
-                    ReceiveMsg::DepositRewards {} => amount.u128(),
-                    _ => 0,
-                }
+        let deposit_msg = HandleMsg::Receive {
+            sender: signer.clone(),
+            from: signer.clone(),
+            amount: Uint128(1_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+
+        // A valid permit reads the signer's deposit
+        let query_msg = QueryMsg::WithPermit {
+            permit: signed_permit(),
+            query: QueryWithPermit::Deposit {},
+        };
+        let result: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match result {
+            QueryAnswer::Deposit { deposit, .. } => {
+                assert_eq!(deposit, Uint128(1_000_000_000_000))
             }
-            _ => 0,
+            _ => panic!("Unexpected result from query"),
         }
+
+        // Tampering with either the signature or the signed params fails verification
+        let mut permit = signed_permit();
+        let mut signature = permit.signature.signature.as_slice().to_vec();
+        signature[10] ^= 1;
+        permit.signature.signature = Binary::from(signature);
+        let query_msg = QueryMsg::WithPermit {
+            permit,
+            query: QueryWithPermit::Deposit {},
+        };
+        assert_eq!(
+            query(&deps, query_msg).unwrap_err(),
+            StdError::from(ContractError::InvalidPermitSignature {})
+        );
+
+        let mut permit = signed_permit();
+        permit.params.permissions = vec![Permission::Owner];
+        let query_msg = QueryMsg::WithPermit {
+            permit,
+            query: QueryWithPermit::Deposit {},
+        };
+        assert_eq!(
+            query(&deps, query_msg).unwrap_err(),
+            StdError::from(ContractError::InvalidPermitSignature {})
+        );
+
+        // The permit only grants the permissions it was signed with
+        let query_msg = QueryMsg::WithPermit {
+            permit: signed_permit(),
+            query: QueryWithPermit::Unbondings {},
+        };
+        assert_eq!(
+            query(&deps, query_msg).unwrap_err(),
+            StdError::from(ContractError::MissingPermission {
+                permit_name: "test".to_string(),
+                permission: "Unbondings".to_string(),
+            })
+        );
+
+        // Only the signer can revoke it
+        let revoke_msg = HandleMsg::RevokePermit {
+            permit_name: "test".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[], 2), revoke_msg.clone()).unwrap();
+        let query_msg = QueryMsg::WithPermit {
+            permit: signed_permit(),
+            query: QueryWithPermit::Deposit {},
+        };
+        assert!(query(&deps, query_msg.clone()).is_ok());
+
+        handle(&mut deps, mock_env(signer.0.clone(), &[], 2), revoke_msg).unwrap();
+        assert_eq!(
+            query(&deps, query_msg).unwrap_err(),
+            StdError::from(ContractError::PermitRevoked {
+                permit_name: "test".to_string(),
+                address: signer,
+            })
+        );
     }
 
-    fn sanity_run(mut rewards: u128, mut deadline: u64) {
-        let mut rng = rand::thread_rng();
+    #[test]
+    fn test_multiple_reward_tokens() {
+        let (init_result, mut deps) = init_helper_with_reward_tokens(1000, vec!["scrt", "atom"]);
+        assert!(init_result.is_ok());
 
-        let (init_result, mut deps) = init_helper(deadline);
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+        deposit_rewards(
+            &mut deps,
+            mock_env("atom", &[], 1),
+            HumanAddr("admin".to_string()),
+            200_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
 
-        deposit_rewards(&mut deps, mock_env("scrt", &[], 1), rewards).unwrap();
+        set_vks(&mut deps, vec![user.clone()]);
+        assert_eq!(
+            query_rewards(&deps, user.clone(), 1000),
+            vec![100_000_000, 200_000_000]
+        );
 
-        let actions = vec!["deposit", "redeem", "deadline", "rewards"];
-        let users = vec![
-            HumanAddr("Lebron James".to_string()),
-            HumanAddr("Kobe Bryant".to_string()),
-            HumanAddr("Giannis".to_string()),
-            HumanAddr("Steph Curry".to_string()),
-            HumanAddr("Deni Avdija".to_string()),
-        ];
+        let redeem_msg = HandleMsg::Redeem {
+            amount: None,
+            recipient: None,
+        };
+        let handle_response = handle(&mut deps, mock_env(user.0, &[], 1000), redeem_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 100_000_000);
+        assert_eq!(extract_transfer(&handle_response, "atom"), 200_000_000);
+        assert_eq!(
+            extract_transfer(&handle_response, "eth"),
+            1_000_000_000_000_000_000
+        );
+    }
 
-        let mut total_rewards_output = 0;
+    #[test]
+    fn test_start_block() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        let mut config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
+        config.start_block = 100;
+        TypedStoreMut::attach(&mut deps.storage)
+            .store(CONFIG_KEY, &config)
+            .unwrap();
 
-        set_vks(&mut deps, users.clone());
-        let mut block: u64 = 2;
-        while block < (deadline + 10_000) {
-            let num_of_actions = rng.gen_range(0, 5);
+        let start_msg = HandleMsg::SetStartBlock { block: 501 };
+        handle(&mut deps, mock_env("admin", &[], 1), start_msg).unwrap();
 
-            for i in 0..num_of_actions {
-                let action_idx = rng.gen_range(0, actions.len());
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg.clone()).unwrap();
 
-                let user_idx = rng.gen_range(0, users.len());
-                let user = users[user_idx].clone();
+        set_vks(&mut deps, vec![user.clone()]);
+        assert_eq!(query_rewards(&deps, user.clone(), 501), vec![0]);
+        assert_eq!(query_rewards(&deps, user.clone(), 751), vec![50_000_000]);
 
-                let (msg, sender) = msg_from_action(&deps, actions[action_idx], user.clone());
-                rewards += extract_reward_deposit(msg.clone());
-                let result = handle(&mut deps, mock_env(sender, &[], block), msg);
-                total_rewards_output += extract_rewards(result);
-            }
+        // Deposits before the start are accepted, but don't earn anything yet
+        let handle_response = handle(&mut deps, mock_env("eth", &[], 400), deposit_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 0);
+        assert_eq!(query_rewards(&deps, user.clone(), 1001), vec![100_000_000]);
 
-            if block % 10000 == 0 {
-                print_status(&deps, users.clone(), block);
-            }
+        let start_msg = HandleMsg::SetStartBlock { block: 600 };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 501), start_msg);
+        assert_eq!(
+            handle_response.unwrap_err(),
+            StdError::from(ContractError::RewardsStarted { start_block: 501 })
+        );
+    }
 
-            deadline = TypedStore::<Config, MockStorage>::attach(&deps.storage)
-                .load(CONFIG_KEY)
-                .unwrap()
-                .deadline;
-            block += 1;
+    #[test]
+    fn test_init_schedule() {
+        for deadline in &[100, 99] {
+            let mut deps = mock_dependencies(20, &[]);
+            let msg = InitMsg {
+                start_block: 100,
+                ..init_msg(*deadline, vec!["scrt"])
+            };
+            assert_eq!(
+                init(&mut deps, mock_env("admin", &[], 1), msg).unwrap_err(),
+                StdError::from(ContractError::InvalidSchedule {
+                    start: 100,
+                    end: *deadline
+                })
+            );
         }
+    }
 
-        // Make sure all users are fully redeemed
-        for user in users {
-            let redeem_msg = HandleMsg::Redeem { amount: None };
-            let result = handle(&mut deps, mock_env(user.0, &[], 1_700_000), redeem_msg);
-            total_rewards_output += extract_rewards(result);
-        }
+    #[test]
+    fn test_earlier_start_block() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        // As if the contract was created with a start block of 500
+        let mut config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
+        config.start_block = 500;
+        TypedStoreMut::attach(&mut deps.storage)
+            .store(CONFIG_KEY, &config)
+            .unwrap();
+        let mut reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        reward_pool.schedules[0].start = 500;
+        TypedStoreMut::attach(&mut deps.storage)
+            .store(REWARD_POOL_KEY, &reward_pool)
+            .unwrap();
 
-        let error = 1.0 - (total_rewards_output as f64 / rewards as f64);
-        println!("Error is: {}", error);
-        assert!(error >= 0f64 && error < 0.01);
-    }
+        let start_msg = HandleMsg::SetStartBlock { block: 101 };
+        handle(&mut deps, mock_env("admin", &[], 1), start_msg).unwrap();
 
-    // Tests
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            90_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
 
+        set_vks(&mut deps, vec![user.clone()]);
+        assert_eq!(query_rewards(&deps, user.clone(), 101), vec![0]);
+        assert_eq!(query_rewards(&deps, user.clone(), 201), vec![10_000_000]);
+        assert_eq!(query_rewards(&deps, user, 1001), vec![90_000_000]);
+    }
     #[test]
-    fn test_claim_pool() {
-        let (init_result, mut deps) = init_helper(10000000); // Claim height is deadline + 1
+    fn test_reward_dust() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
 
-        let claim_msg = HandleMsg::ClaimRewardPool { to: None };
-        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 10), claim_msg.clone());
+        // Deposits of any size are accepted
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            1000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let users = vec![
+            HumanAddr("alice".to_string()),
+            HumanAddr("bob".to_string()),
+            HumanAddr("carol".to_string()),
+        ];
+        for user in &users {
+            let deposit_msg = HandleMsg::Receive {
+                sender: user.clone(),
+                from: user.clone(),
+                amount: Uint128(1_000_000_000_000_000_000_000_000),
+                msg: to_binary(&ReceiveMsg::Deposit {
+                    lock_blocks: None,
+                    beneficiary: None,
+                })
+                .unwrap(),
+            };
+            handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
+        }
+
+        // 1000 rewards can't be split evenly between 3 * 10^12 shares
+        for user in &users {
+            let claim_msg = HandleMsg::ClaimRewards { recipient: None };
+            let handle_response =
+                handle(&mut deps, mock_env(user.0.clone(), &[], 1001), claim_msg).unwrap();
+            assert_eq!(extract_transfer(&handle_response, "scrt"), 333);
+        }
+        let result: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::RewardDust {}).unwrap()).unwrap();
+        match result {
+            QueryAnswer::RewardDust { dust } => assert_eq!(dust[0].amount, Uint128(1)),
+            _ => panic!("NOPE"),
+        }
+
+        let sweep_msg = HandleMsg::SweepDust { to: None };
+        let handle_response = handle(&mut deps, mock_env("user", &[], 1001), sweep_msg.clone());
         assert_eq!(
             handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "not an admin: not_admin".to_string(),
-                backtrace: None
-            }
+            StdError::from(ContractError::MissingRole {
+                address: HumanAddr("user".to_string()),
+                role: Role::Treasury
+            })
         );
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 1001), sweep_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 1);
 
-        let handle_response = handle(&mut deps, mock_env("admin", &[], 10), claim_msg.clone());
+        let result: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::RewardDust {}).unwrap()).unwrap();
+        match result {
+            QueryAnswer::RewardDust { dust } => assert_eq!(dust[0].amount, Uint128(0)),
+            _ => panic!("NOPE"),
+        }
+    }
+
+    #[test]
+    fn test_allowed_funders() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
+
+        let funder = HumanAddr("funder".to_string());
+        let funders_msg = HandleMsg::SetAllowedFunders {
+            funders: Some(vec![funder.clone()]),
+        };
+        let handle_response = handle(&mut deps, mock_env("user", &[], 1), funders_msg.clone());
         assert_eq!(
             handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: format!("minimum claim height hasn't passed yet: {}", 10000001),
-                backtrace: None
-            }
+            StdError::from(ContractError::MissingRole {
+                address: HumanAddr("user".to_string()),
+                role: Role::CampaignManager
+            })
         );
-
+        handle(&mut deps, mock_env("admin", &[], 1), funders_msg).unwrap();
+
+        let rewards_msg = |from: &HumanAddr, amount: u128| HandleMsg::Receive {
+            sender: from.clone(),
+            from: from.clone(),
+            amount: Uint128(amount),
+            msg: to_binary(&ReceiveMsg::DepositRewards {
+                start: None,
+                end: None,
+            })
+            .unwrap(),
+        };
+        let stranger = HumanAddr("stranger".to_string());
         let handle_response = handle(
             &mut deps,
-            mock_env("admin", &[], 10000001),
-            claim_msg.clone(),
+            mock_env("scrt", &[], 5),
+            rewards_msg(&stranger, 100_000_000),
         );
         assert_eq!(
             handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "Error performing Balance query: Generic error: Querier system error: No such contract: scrt".to_string(), // No way to test external queries yet
-                backtrace: None
-            }
+            StdError::from(ContractError::FunderNotAllowed { address: stranger })
         );
+
+        handle(
+            &mut deps,
+            mock_env("scrt", &[], 5),
+            rewards_msg(&funder, 100_000_000),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            mock_env("scrt", &[], 10),
+            rewards_msg(&funder, 200_000_000),
+        )
+        .unwrap();
+
+        let result: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::AllowedFunders {}).unwrap()).unwrap();
+        match result {
+            QueryAnswer::AllowedFunders { funders } => {
+                assert_eq!(funders, Some(vec![funder.clone()]))
+            }
+            _ => panic!("NOPE"),
+        }
+
+        let query_msg = QueryMsg::FundingHistory {
+            start: Some(1),
+            limit: Some(10),
+        };
+        let result: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match result {
+            QueryAnswer::FundingHistory { fundings, total } => {
+                assert_eq!(total, 2);
+                assert_eq!(
+                    fundings,
+                    vec![FundingInfo {
+                        funder,
+                        token: HumanAddr("scrt".to_string()),
+                        amount: Uint128(200_000_000),
+                        block: 10,
+                    }]
+                );
+            }
+            _ => panic!("NOPE"),
+        }
     }
 
     #[test]
-    fn test_stop_contract() {
-        let (init_result, mut deps) = init_helper(10000000);
+    fn test_reward_schedules() {
+        let (init_result, mut deps) = init_helper(1000);
+        assert!(init_result.is_ok());
 
-        let stop_msg = HandleMsg::StopContract {};
-        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 10), stop_msg.clone());
-        assert_eq!(
-            handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "not an admin: not_admin".to_string(),
-                backtrace: None
-            }
-        );
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            Some(2000),
+            Some(3000),
+        )
+        .unwrap();
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            200_000_000,
+            Some(3000),
+            Some(4000),
+        )
+        .unwrap();
+
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
+        };
+        handle(&mut deps, mock_env("eth", &[], 1), deposit_msg).unwrap();
 
-        let handle_response = handle(&mut deps, mock_env("admin", &[], 10), stop_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::StopContract { status: Success }).unwrap()
-        );
+        let query_msg = QueryMsg::UpcomingSchedules { height: 1500 };
+        let result: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match result {
+            QueryAnswer::RewardSchedules { schedules, .. } => assert_eq!(
+                schedules
+                    .iter()
+                    .map(|schedule| (schedule.start, schedule.end, schedule.rewards[0].amount))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (2000, 3000, Uint128(100_000_000)),
+                    (3000, 4000, Uint128(200_000_000))
+                ]
+            ),
+            _ => panic!("NOPE"),
+        }
 
-        let redeem_msg = HandleMsg::Redeem { amount: None };
-        let handle_response = handle(&mut deps, mock_env("user", &[], 20), redeem_msg);
+        set_vks(&mut deps, vec![user.clone()]);
+        assert_eq!(query_rewards(&deps, user.clone(), 1500), vec![0]);
+        assert_eq!(query_rewards(&deps, user.clone(), 2500), vec![50_000_000]);
+        assert_eq!(query_rewards(&deps, user.clone(), 3500), vec![200_000_000]);
+
+        let query_msg = QueryMsg::EndHeight {};
+        let result: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match result {
+            QueryAnswer::EndHeight { height } => assert_eq!(height, 4000),
+            _ => panic!("NOPE"),
+        }
+
+        let redeem_msg = HandleMsg::Redeem {
+            amount: None,
+            recipient: None,
+        };
+        let handle_response = handle(&mut deps, mock_env(user.0, &[], 4000), redeem_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 300_000_000);
+
+        let query_msg = QueryMsg::ActiveSchedules { height: 4000 };
+        let result: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match result {
+            QueryAnswer::RewardSchedules { schedules, .. } => assert!(schedules.is_empty()),
+            _ => panic!("NOPE"),
+        }
+    }
+
+    #[test]
+    fn test_reward_schedule_limits() {
+        let (init_result, mut deps) = init_helper(1000);
+        assert!(init_result.is_ok());
+
+        let result = deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            0,
+            None,
+            None,
+        );
         assert_eq!(
-            handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "this contract is stopped and this action is not allowed".to_string(),
-                backtrace: None
-            }
+            result.unwrap_err(),
+            StdError::from(ContractError::ZeroRewards {})
         );
 
-        let resume_msg = HandleMsg::ResumeContract {};
-        let handle_response = handle(&mut deps, mock_env("admin", &[], 21), resume_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
+        // Only campaign managers can add schedules
+        let result = deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("funder".to_string()),
+            100,
+            None,
+            Some(2000),
+        );
         assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::ResumeContract { status: Success }).unwrap()
+            result.unwrap_err(),
+            StdError::from(ContractError::MissingRole {
+                address: HumanAddr("funder".to_string()),
+                role: Role::CampaignManager,
+            })
         );
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("funder".to_string()),
+            100,
+            None,
+            None,
+        )
+        .unwrap();
 
-        let redeem_msg = HandleMsg::Redeem { amount: None };
-        let handle_response = handle(&mut deps, mock_env("user", &[], 20), redeem_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
+        let schedules = TypedStore::<RewardPool, _>::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap()
+            .schedules
+            .len() as u64;
+        for i in schedules..MAX_REWARD_SCHEDULES as u64 {
+            deposit_rewards(
+                &mut deps,
+                mock_env("scrt", &[], 1),
+                HumanAddr("admin".to_string()),
+                100,
+                Some(2000 + i),
+                Some(3000),
+            )
+            .unwrap();
+        }
+        let result = deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100,
+            Some(1000),
+            Some(3000),
+        );
         assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::Redeem { status: Success }).unwrap()
+            result.unwrap_err(),
+            StdError::from(ContractError::TooManySchedules {
+                max: MAX_REWARD_SCHEDULES
+            })
         );
+
+        // Existing schedules can still be topped up
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100,
+            Some(2000 + schedules),
+            Some(3000),
+        )
+        .unwrap();
     }
 
     #[test]
-    fn test_admin() {
-        let (init_result, mut deps) = init_helper(10000000);
+    fn test_finished_schedule_leftovers() {
+        let (init_result, mut deps) = init_helper(1001);
+        assert!(init_result.is_ok());
 
-        let admin_action_msg = HandleMsg::ChangeAdmin {
-            address: HumanAddr("not_admin".to_string()),
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            Some(101),
+        )
+        .unwrap();
+        deposit_rewards(
+            &mut deps,
+            mock_env("scrt", &[], 1),
+            HumanAddr("admin".to_string()),
+            100_000_000,
+            None,
+            None,
+        )
+        .unwrap();
+
+        // The first schedule ends while nothing is locked, so its rewards move to the second one
+        let user = HumanAddr("user".to_string());
+        let deposit_msg = HandleMsg::Receive {
+            sender: user.clone(),
+            from: user.clone(),
+            amount: Uint128(1_000_000_000_000_000_000),
+            msg: to_binary(&ReceiveMsg::Deposit {
+                lock_blocks: None,
+                beneficiary: None,
+            })
+            .unwrap(),
         };
-        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 1), admin_action_msg);
+        handle(&mut deps, mock_env("eth", &[], 201), deposit_msg).unwrap();
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
         assert_eq!(
-            handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "not an admin: not_admin".to_string(),
-                backtrace: None
-            }
+            reward_pool.schedules,
+            vec![RewardSchedule {
+                start: 0,
+                end: 1001,
+                amounts: vec![200_000_000],
+            }]
         );
 
-        let admin_action_msg = HandleMsg::ChangeAdmin {
-            address: HumanAddr("new_admin".to_string()),
-        };
-        let handle_response = handle(&mut deps, mock_env("admin", &[], 1), admin_action_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::ChangeAdmin { status: Success }).unwrap()
-        );
+        let claim_msg = HandleMsg::ClaimRewards { recipient: None };
+        let handle_response =
+            handle(&mut deps, mock_env(user.0.clone(), &[], 1001), claim_msg).unwrap();
+        assert_eq!(extract_transfer(&handle_response, "scrt"), 200_000_000);
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.rewards[0].pending_rewards, 0);
 
-        let admin_action_msg = HandleMsg::ChangeAdmin {
-            address: HumanAddr("not_admin".to_string()),
-        };
-        let handle_response = handle(&mut deps, mock_env("admin", &[], 1), admin_action_msg);
+        let deadline_msg = HandleMsg::SetDeadline { block: 1001 };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 1002), deadline_msg);
         assert_eq!(
             handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "not an admin: admin".to_string(),
-                backtrace: None
-            }
-        );
-
-        let admin_action_msg = HandleMsg::ChangeAdmin {
-            address: HumanAddr("not_admin".to_string()),
-        };
-        let handle_response = handle(&mut deps, mock_env("new_admin", &[], 1), admin_action_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::ChangeAdmin { status: Success }).unwrap()
+            StdError::from(ContractError::InvalidSchedule {
+                start: 1002,
+                end: 1001
+            })
         );
     }
-
     #[test]
     fn test_single_run() {
         let mut rng = rand::thread_rng();
@@ -1159,6 +5701,55 @@ mod tests {
         }
     }
 
+    /// Answers SNIP20 queries. Balance queries are answered with `balance`, and the token is always "Ether"
+    struct Snip20Querier {
+        balance: u128,
+    }
+
+    impl Querier for Snip20Querier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = from_slice(bin_request).unwrap();
+            let msg = match request {
+                QueryRequest::Wasm(WasmQuery::Smart { msg, .. }) => msg,
+                _ => panic!("Unexpected query: {:?}", request),
+            };
+            match from_binary(&msg).unwrap() {
+                Snip20QueryMsg::Balance { .. } => Ok(to_binary(&Snip20QueryAnswer::Balance {
+                    amount: Uint128(self.balance),
+                })),
+                Snip20QueryMsg::TokenInfo {} => Ok(to_binary(&Snip20QueryAnswer::TokenInfo {
+                    name: "Ether".to_string(),
+                    symbol: "ETH".to_string(),
+                    decimals: 18,
+                    total_supply: None,
+                })),
+            }
+        }
+    }
+
+    /// SNIP20 token queries
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Snip20QueryMsg {
+        Balance { address: HumanAddr, key: String },
+        TokenInfo {},
+    }
+
+    /// SNIP20 token query answers
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Snip20QueryAnswer {
+        Balance {
+            amount: Uint128,
+        },
+        TokenInfo {
+            name: String,
+            symbol: String,
+            decimals: u8,
+            total_supply: Option<Uint128>,
+        },
+    }
+
     /// SNIP20 token handle messages
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
     #[serde(rename_all = "snake_case")]
@@ -0,0 +1,209 @@
+use cosmwasm_std::{to_vec, HumanAddr, StdError};
+use serde::Serialize;
+use snafu::Snafu;
+
+use crate::state::{ContractStatus, Role};
+
+/// ContractError is every failure this contract reports on its own
+///
+/// Each variant has a stable code, returned by `ContractError::code`. Errors reach clients as a generic error
+/// with a JSON message, e.g. `{"code":"insufficient_funds","msg":"insufficient funds to redeem: ..."}`, so
+/// they can match on the code instead of parsing the message.
+#[derive(Snafu, Debug)]
+pub enum ContractError {
+    #[snafu(display("invalid configuration: {}", reason))]
+    InvalidConfig { reason: String },
+
+    #[snafu(display("this action is not allowed while the contract status is {:?}", status))]
+    ContractStopped { status: ContractStatus },
+
+    #[snafu(display("can't migrate from a newer contract version: {}", version))]
+    UnknownVersion { version: u32 },
+
+    #[snafu(display("Unavailable or unknown action"))]
+    UnknownAction {},
+
+    #[snafu(display("not an admin: {}", address))]
+    Unauthorized { address: HumanAddr },
+
+    #[snafu(display("{} doesn't have the {:?} role", address, role))]
+    MissingRole { address: HumanAddr, role: Role },
+
+    #[snafu(display("not the pending admin: {}", address))]
+    NotPendingAdmin { address: HumanAddr },
+
+    #[snafu(display("permit doesn't apply to this contract: {}", contract_address))]
+    PermitNotForContract { contract_address: HumanAddr },
+
+    #[snafu(display("permit {:?} was revoked by {}", permit_name, address))]
+    PermitRevoked {
+        permit_name: String,
+        address: HumanAddr,
+    },
+
+    #[snafu(display("failed to verify permit signature"))]
+    InvalidPermitSignature {},
+
+    #[snafu(display(
+        "permit {:?} doesn't allow this query, missing permission: {}",
+        permit_name,
+        permission
+    ))]
+    MissingPermission {
+        permit_name: String,
+        permission: String,
+    },
+
+    #[snafu(display(
+        "This token is not supported. Supported: {}, given: {}",
+        supported,
+        given
+    ))]
+    UnsupportedToken { supported: String, given: HumanAddr },
+
+    #[snafu(display("{} is not allowed to deposit rewards", address))]
+    FunderNotAllowed { address: HumanAddr },
+
+    #[snafu(display("lockup is too long: max={}, given={}", max, given))]
+    LockupTooLong { max: u64, given: u64 },
+
+    #[snafu(display("can't transfer a position to its own owner"))]
+    TransferToSelf {},
+
+    #[snafu(display("only {} can lock its tokens", address))]
+    LockupExtensionNotAllowed { address: HumanAddr },
+
+    #[snafu(display("schedule must end after it starts: start={}, end={}", start, end))]
+    InvalidSchedule { start: u64, end: u64 },
+
+    #[snafu(display("a schedule that has a start must also have an end"))]
+    MissingScheduleEnd {},
+
+    #[snafu(display("can't have more than {} reward schedules", max))]
+    TooManySchedules { max: usize },
+
+    #[snafu(display("can't deposit zero rewards"))]
+    ZeroRewards {},
+
+    #[snafu(display(
+        "insufficient funds to redeem: balance={}, required={}",
+        balance,
+        required
+    ))]
+    InsufficientFunds { balance: u128, required: u128 },
+
+    #[snafu(display(
+        "insufficient allowance: allowance={}, required={}",
+        allowance,
+        required
+    ))]
+    InsufficientAllowance { allowance: u128, required: u128 },
+
+    #[snafu(display("{} is neither the owner nor the spender of the allowance", address))]
+    NotAllowanceParty { address: HumanAddr },
+
+    #[snafu(display("{} has no tokens locked", address))]
+    NoDeposit { address: HumanAddr },
+
+    #[snafu(display("tokens are locked until block {}", unlock_height))]
+    TokensLocked { unlock_height: u64 },
+
+    #[snafu(display("{} doesn't have an expired boost", address))]
+    NothingToKick { address: HumanAddr },
+
+    #[snafu(display("minimum claim height hasn't passed yet: {}", claim_block))]
+    ClaimTooEarly { claim_block: u64 },
+
+    #[snafu(display("rewards already started at block {}", start_block))]
+    RewardsStarted { start_block: u64 },
+
+    #[snafu(display("start block can't be in the past: {}", block))]
+    StartBlockInPast { block: u64 },
+
+    #[snafu(display(
+        "height {} is before the last reward update at block {}",
+        height,
+        last_reward_block
+    ))]
+    HeightTooOld { height: u64, last_reward_block: u64 },
+
+    #[snafu(display(
+        "height {} is too far ahead of the last reward update at block {}",
+        height,
+        last_reward_block
+    ))]
+    HeightTooFarAhead { height: u64, last_reward_block: u64 },
+
+    #[snafu(display(
+        "compounding is only possible when rewards are paid in the incentivized token"
+    ))]
+    CompoundingUnavailable {},
+
+    #[snafu(display("arithmetic overflow"))]
+    Overflow {},
+
+    #[snafu(display("arithmetic underflow"))]
+    Underflow {},
+}
+
+impl ContractError {
+    /// A stable, machine-readable identifier of the error. These must never change once released
+    pub fn code(&self) -> &'static str {
+        match self {
+            ContractError::InvalidConfig { .. } => "invalid_config",
+            ContractError::ContractStopped { .. } => "contract_stopped",
+            ContractError::UnknownVersion { .. } => "unknown_version",
+            ContractError::UnknownAction {} => "unknown_action",
+            ContractError::Unauthorized { .. } => "unauthorized",
+            ContractError::MissingRole { .. } => "missing_role",
+            ContractError::NotPendingAdmin { .. } => "not_pending_admin",
+            ContractError::PermitNotForContract { .. } => "permit_not_for_contract",
+            ContractError::PermitRevoked { .. } => "permit_revoked",
+            ContractError::InvalidPermitSignature {} => "invalid_permit_signature",
+            ContractError::MissingPermission { .. } => "missing_permission",
+            ContractError::UnsupportedToken { .. } => "unsupported_token",
+            ContractError::FunderNotAllowed { .. } => "funder_not_allowed",
+            ContractError::LockupTooLong { .. } => "lockup_too_long",
+            ContractError::TransferToSelf {} => "transfer_to_self",
+            ContractError::LockupExtensionNotAllowed { .. } => "lockup_extension_not_allowed",
+            ContractError::InvalidSchedule { .. } => "invalid_schedule",
+            ContractError::MissingScheduleEnd {} => "missing_schedule_end",
+            ContractError::TooManySchedules { .. } => "too_many_schedules",
+            ContractError::ZeroRewards {} => "zero_rewards",
+            ContractError::InsufficientFunds { .. } => "insufficient_funds",
+            ContractError::InsufficientAllowance { .. } => "insufficient_allowance",
+            ContractError::NotAllowanceParty { .. } => "not_allowance_party",
+            ContractError::NoDeposit { .. } => "no_deposit",
+            ContractError::TokensLocked { .. } => "tokens_locked",
+            ContractError::NothingToKick { .. } => "nothing_to_kick",
+            ContractError::ClaimTooEarly { .. } => "claim_too_early",
+            ContractError::RewardsStarted { .. } => "rewards_started",
+            ContractError::StartBlockInPast { .. } => "start_block_in_past",
+            ContractError::HeightTooOld { .. } => "height_too_old",
+            ContractError::HeightTooFarAhead { .. } => "height_too_far_ahead",
+            ContractError::CompoundingUnavailable {} => "compounding_unavailable",
+            ContractError::Overflow {} => "overflow",
+            ContractError::Underflow {} => "underflow",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorMessage<'a> {
+    code: &'a str,
+    msg: String,
+}
+
+impl From<ContractError> for StdError {
+    fn from(err: ContractError) -> Self {
+        let message = ErrorMessage {
+            code: err.code(),
+            msg: err.to_string(),
+        };
+        // Serializing a struct of strings can't fail, but falling back to the plain message costs nothing
+        match to_vec(&message) {
+            Ok(json) => StdError::generic_err(String::from_utf8_lossy(&json)),
+            Err(_) => StdError::generic_err(message.msg),
+        }
+    }
+}
@@ -1,6 +1,9 @@
 mod constants;
 pub mod contract;
+pub mod error;
+pub mod migrate;
 pub mod msg;
+pub mod permit;
 pub mod state;
 pub mod utils;
 pub mod viewing_key;
@@ -9,7 +12,7 @@ pub mod viewing_key;
 mod wasm {
     use super::contract;
     use cosmwasm_std::{
-        do_handle, do_init, do_query, ExternalApi, ExternalQuerier, ExternalStorage,
+        do_handle, do_init, do_migrate, do_query, ExternalApi, ExternalQuerier, ExternalStorage,
     };
 
     #[no_mangle]
@@ -30,6 +33,15 @@ mod wasm {
         )
     }
 
+    #[no_mangle]
+    extern "C" fn migrate(env_ptr: u32, msg_ptr: u32) -> u32 {
+        do_migrate(
+            &contract::migrate::<ExternalStorage, ExternalApi, ExternalQuerier>,
+            env_ptr,
+            msg_ptr,
+        )
+    }
+
     #[no_mangle]
     extern "C" fn query(msg_ptr: u32) -> u32 {
         do_query(
@@ -0,0 +1,128 @@
+use cosmwasm_std::{HumanAddr, StdResult};
+
+use crate::constants::BOOST_SCALE;
+use crate::error::ContractError;
+use crate::state::{Config, ContractStatus, RewardPool, RewardSchedule, TokenRewards, UserInfo};
+
+/// Layouts of the first release, which didn't store a contract version. Both scales were constants then
+pub mod v0 {
+    use cosmwasm_std::HumanAddr;
+    use serde::{Deserialize, Serialize};
+
+    use crate::state::Snip20;
+
+    pub const INC_TOKEN_SCALE: u128 = 1_000_000_000_000;
+    pub const REWARD_SCALE: u128 = 1_000_000_000_000;
+
+    /// `locked` - Amount of the incentivized token locked by the user, scaled down by `INC_TOKEN_SCALE`.
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct UserInfo {
+        pub locked: u128,
+        pub debt: u128,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct Config {
+        pub admin: HumanAddr,
+        pub reward_token: Snip20,
+        pub inc_token: Snip20,
+        pub pool_claim_block: u64,
+        pub deadline: u64,
+        pub viewing_key: String,
+        pub prng_seed: Vec<u8>,
+        pub is_stopped: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct RewardPool {
+        pub pending_rewards: u128,
+        pub inc_token_supply: u128,
+        pub last_reward_block: u64,
+        pub acc_reward_per_share: u128,
+    }
+}
+
+/// A stopped contract only allowed emergency redeems, which is what `EmergencyOnly` keeps
+pub fn upgrade_config(config: v0::Config, contract_address: HumanAddr) -> Config {
+    Config {
+        admin: config.admin,
+        pending_admin: None,
+        contract_address,
+        reward_tokens: vec![config.reward_token],
+        inc_token: config.inc_token,
+        inc_token_scale: v0::INC_TOKEN_SCALE,
+        reward_scale: v0::REWARD_SCALE,
+        max_lock_blocks: 0,
+        max_boost: BOOST_SCALE,
+        unbonding_period: 0,
+        start_block: 0,
+        pool_claim_block: config.pool_claim_block,
+        viewing_key: config.viewing_key,
+        prng_seed: config.prng_seed,
+        status: if config.is_stopped {
+            ContractStatus::EmergencyOnly
+        } else {
+            ContractStatus::Normal
+        },
+        allowed_funders: None,
+    }
+}
+
+/// The rewards left become a single schedule that ends at the old deadline. The first release didn't track what
+/// it owed the users, so the `reward_balance` that isn't scheduled, or locked when the reward token is also the
+/// incentivized token, is owed to them up to what the current shares accrued. The rest is mostly the 1 SCRT the
+/// first release held back from every reward deposit, and becomes dust
+pub fn upgrade_reward_pool(
+    reward_pool: v0::RewardPool,
+    config: &Config,
+    deadline: u64,
+    reward_balance: u128,
+) -> StdResult<RewardPool> {
+    let inc_token_locked = reward_pool
+        .inc_token_supply
+        .checked_mul(v0::INC_TOKEN_SCALE)
+        .ok_or(ContractError::Overflow {})?;
+    let mut owed = reward_balance.saturating_sub(reward_pool.pending_rewards);
+    if config.reward_tokens[0].address == config.inc_token.address {
+        owed = owed.saturating_sub(inc_token_locked);
+    }
+    let owed = owed
+        .checked_mul(v0::REWARD_SCALE)
+        .ok_or(ContractError::Overflow {})?;
+    // Debts only lower what users can claim, so they can't claim more than this
+    let accrued = reward_pool
+        .inc_token_supply
+        .checked_mul(reward_pool.acc_reward_per_share)
+        .ok_or(ContractError::Overflow {})?;
+    let unclaimed = std::cmp::min(owed, accrued);
+
+    Ok(RewardPool {
+        inc_token_supply: reward_pool.inc_token_supply,
+        inc_token_locked,
+        inc_token_unbonding: 0,
+        last_reward_block: reward_pool.last_reward_block,
+        rewards: vec![TokenRewards {
+            pending_rewards: reward_pool.pending_rewards,
+            acc_reward_per_share: reward_pool.acc_reward_per_share,
+            dust: owed - unclaimed,
+            unclaimed,
+        }],
+        schedules: vec![RewardSchedule {
+            start: 0,
+            end: deadline,
+            amounts: vec![reward_pool.pending_rewards],
+        }],
+    })
+}
+
+pub fn upgrade_user(user: v0::UserInfo) -> StdResult<UserInfo> {
+    Ok(UserInfo {
+        locked: user
+            .locked
+            .checked_mul(v0::INC_TOKEN_SCALE)
+            .ok_or(ContractError::Overflow {})?,
+        shares: user.locked,
+        debt: vec![user.debt],
+        ..UserInfo::new(1)
+    })
+}
@@ -1,4 +1,5 @@
-use crate::state::Snip20;
+use crate::permit::Permit;
+use crate::state::{ContractStatus, Role, Snip20};
 use crate::viewing_key::ViewingKey;
 use cosmwasm_std::{Binary, HumanAddr, Uint128};
 use schemars::JsonSchema;
@@ -6,19 +7,32 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
-    pub reward_token: Snip20,
+    pub reward_tokens: Vec<Snip20>,
     pub inc_token: Snip20,
+    pub inc_token_scale: Uint128,
+    pub reward_scale: Uint128,
+    pub max_lock_blocks: u64,
+    pub max_boost: u64,
+    pub unbonding_period: u64,
+    pub start_block: u64,
     pub deadline: u64,
     pub pool_claim_block: u64,
+    pub allowed_funders: Option<Vec<HumanAddr>>,
     pub viewing_key: String,
     pub prng_seed: Binary,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
+    /// Payouts go to `recipient`, or to the sender when it isn't set. This applies to every message that pays the
+    /// sender's tokens or rewards
     Redeem {
         amount: Option<Uint128>,
+        recipient: Option<HumanAddr>,
     },
     CreateViewingKey {
         entropy: String,
@@ -28,7 +42,82 @@ pub enum HandleMsg {
         key: String,
         padding: Option<String>,
     },
-    EmergencyRedeem {},
+    RevokePermit {
+        permit_name: String,
+        padding: Option<String>,
+    },
+    ClaimUnbonded {
+        recipient: Option<HumanAddr>,
+    },
+    ClaimRewards {
+        recipient: Option<HumanAddr>,
+    },
+    /// Moves `amount` of the sender's deposit, or the whole position, to `recipient`. Only works once the
+    /// sender's tokens are unlocked
+    TransferPosition {
+        recipient: HumanAddr,
+        amount: Option<Uint128>,
+    },
+
+    // SNIP-20 commands over the locked tokens. Transfers pay both parties' pending rewards. A recipient can't
+    // refuse a transfer, so only unlocked tokens can be transferred
+    Transfer {
+        recipient: HumanAddr,
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    Send {
+        recipient: HumanAddr,
+        recipient_code_hash: Option<String>,
+        amount: Uint128,
+        msg: Option<Binary>,
+        padding: Option<String>,
+    },
+    TransferFrom {
+        owner: HumanAddr,
+        recipient: HumanAddr,
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    SendFrom {
+        owner: HumanAddr,
+        recipient: HumanAddr,
+        recipient_code_hash: Option<String>,
+        amount: Uint128,
+        msg: Option<Binary>,
+        padding: Option<String>,
+    },
+    IncreaseAllowance {
+        spender: HumanAddr,
+        amount: Uint128,
+        expiration: Option<u64>,
+        padding: Option<String>,
+    },
+    DecreaseAllowance {
+        spender: HumanAddr,
+        amount: Uint128,
+        expiration: Option<u64>,
+        padding: Option<String>,
+    },
+    RegisterReceive {
+        code_hash: String,
+        padding: Option<String>,
+    },
+
+    Compound {},
+    SetAutoCompound {
+        enabled: bool,
+    },
+    CompoundAll {
+        start: Option<u32>,
+        limit: Option<u32>,
+    },
+    Kick {
+        address: HumanAddr,
+    },
+    EmergencyRedeem {
+        recipient: Option<HumanAddr>,
+    },
 
     // Registered commands
     Receive {
@@ -42,12 +131,35 @@ pub enum HandleMsg {
     SetDeadline {
         block: u64,
     },
+    SetStartBlock {
+        block: u64,
+    },
     ClaimRewardPool {
         to: Option<HumanAddr>,
     },
-    StopContract {},
-    ResumeContract {},
-    ChangeAdmin {
+    SetAllowedFunders {
+        funders: Option<Vec<HumanAddr>>,
+    },
+    SweepDust {
+        to: Option<HumanAddr>,
+    },
+    SetContractStatus {
+        status: ContractStatus,
+    },
+    PayFrozenRewards {
+        address: HumanAddr,
+    },
+    ProposeAdmin {
+        address: HumanAddr,
+    },
+    AcceptAdmin {},
+    CancelAdminProposal {},
+    GrantRole {
+        role: Role,
+        address: HumanAddr,
+    },
+    RevokeRole {
+        role: Role,
         address: HumanAddr,
     },
 }
@@ -56,21 +168,65 @@ pub enum HandleMsg {
 #[serde(rename_all = "snake_case")]
 pub enum HandleAnswer {
     Redeem { status: ResponseStatus },
+    ClaimUnbonded { status: ResponseStatus },
+    ClaimRewards { rewards: Vec<RewardAmount> },
+    TransferPosition { status: ResponseStatus },
+    Transfer { status: ResponseStatus },
+    Send { status: ResponseStatus },
+    TransferFrom { status: ResponseStatus },
+    SendFrom { status: ResponseStatus },
+    IncreaseAllowance { allowance: Uint128 },
+    DecreaseAllowance { allowance: Uint128 },
+    RegisterReceive { status: ResponseStatus },
+    Compound { status: ResponseStatus },
+    SetAutoCompound { status: ResponseStatus },
+    CompoundAll { status: ResponseStatus },
+    Kick { status: ResponseStatus },
     CreateViewingKey { key: ViewingKey },
     SetViewingKey { status: ResponseStatus },
-    StopContract { status: ResponseStatus },
-    ResumeContract { status: ResponseStatus },
-    ChangeAdmin { status: ResponseStatus },
+    RevokePermit { status: ResponseStatus },
+    SetContractStatus { status: ResponseStatus },
+    ProposeAdmin { status: ResponseStatus },
+    AcceptAdmin { status: ResponseStatus },
+    CancelAdminProposal { status: ResponseStatus },
+    GrantRole { status: ResponseStatus },
+    RevokeRole { status: ResponseStatus },
     SetDeadline { status: ResponseStatus },
+    SetStartBlock { status: ResponseStatus },
+    SetAllowedFunders { status: ResponseStatus },
+    SweepDust { status: ResponseStatus },
     ClaimRewardPool { status: ResponseStatus },
     EmergencyRedeem { status: ResponseStatus },
+    PayFrozenRewards { status: ResponseStatus },
+}
+
+/// The callback `Send` and `SendFrom` make to a recipient contract, as in SNIP-20
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiverHandleMsg {
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        msg: Option<Binary>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ReceiveMsg {
-    Deposit {},
-    DepositRewards {},
+    /// Locks the sent tokens for `beneficiary`, or for the sender when it isn't set. Only the beneficiary
+    /// itself can set `lock_blocks`
+    Deposit {
+        lock_blocks: Option<u64>,
+        beneficiary: Option<HumanAddr>,
+    },
+    /// Adds the rewards to the schedule that ends last, or to a new one from `start` to `end`. Only campaign
+    /// managers can add schedules
+    DepositRewards {
+        start: Option<u64>,
+        end: Option<u64>,
+    },
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
@@ -86,12 +242,37 @@ pub enum QueryMsg {
     TokenInfo {},
     ClaimBlock {},
     ContractStatus {},
-    RewardToken {},
+    PendingAdmin {},
+    Roles {},
+    RewardTokens {},
     IncentivizedToken {},
+    StartHeight {},
     EndHeight {},
     RewardPoolBalance {},
+    RewardDust {},
+    ActiveSchedules {
+        height: u64,
+    },
+    UpcomingSchedules {
+        height: u64,
+    },
+    AllowedFunders {},
+    FundingHistory {
+        start: Option<u32>,
+        limit: Option<u32>,
+    },
 
     // Authenticated
+    Balance {
+        address: HumanAddr,
+        key: String,
+    },
+    /// Authenticated with the viewing key of either the owner or the spender
+    Allowance {
+        owner: HumanAddr,
+        spender: HumanAddr,
+        key: String,
+    },
     Rewards {
         address: HumanAddr,
         height: u64,
@@ -101,18 +282,49 @@ pub enum QueryMsg {
         address: HumanAddr,
         key: String,
     },
+    Unbondings {
+        address: HumanAddr,
+        key: String,
+    },
+    WithPermit {
+        permit: Permit,
+        query: QueryWithPermit,
+    },
 }
 
 impl QueryMsg {
-    pub fn get_validation_params(&self) -> (&HumanAddr, ViewingKey) {
+    /// The addresses whose viewing key may authenticate the query, and the given key
+    pub fn get_validation_params(&self) -> (Vec<&HumanAddr>, ViewingKey) {
         match self {
-            QueryMsg::Rewards { address, key, .. } => (address, ViewingKey(key.clone())),
-            QueryMsg::Deposit { address, key } => (address, ViewingKey(key.clone())),
+            QueryMsg::Balance { address, key } => (vec![address], ViewingKey(key.clone())),
+            QueryMsg::Allowance {
+                owner,
+                spender,
+                key,
+            } => (vec![owner, spender], ViewingKey(key.clone())),
+            QueryMsg::Rewards { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            QueryMsg::Deposit { address, key } => (vec![address], ViewingKey(key.clone())),
+            QueryMsg::Unbondings { address, key } => (vec![address], ViewingKey(key.clone())),
             _ => panic!("This should never happen"),
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    Balance {},
+    Allowance {
+        owner: HumanAddr,
+        spender: HumanAddr,
+    },
+    Rewards {
+        height: u64,
+    },
+    Deposit {},
+    Unbondings {},
+}
+
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryAnswer {
@@ -122,29 +334,68 @@ pub enum QueryAnswer {
         decimals: u8,
         total_supply: Option<Uint128>,
     },
+    Balance {
+        amount: Uint128,
+    },
+    Allowance {
+        owner: HumanAddr,
+        spender: HumanAddr,
+        allowance: Uint128,
+        expiration: Option<u64>,
+    },
     Rewards {
-        rewards: Uint128,
+        rewards: Vec<RewardAmount>,
+        height: u64,
+        last_reward_block: u64,
     },
     Deposit {
         deposit: Uint128,
+        unlock_height: u64,
+        boost: u64,
+    },
+    Unbondings {
+        unbondings: Vec<UnbondingInfo>,
     },
     ClaimBlock {
         height: u64,
     },
     ContractStatus {
-        is_stopped: bool,
+        status: ContractStatus,
     },
-    RewardToken {
-        token: Snip20,
+    PendingAdmin {
+        address: Option<HumanAddr>,
+    },
+    Roles {
+        roles: Vec<RoleInfo>,
+    },
+    RewardTokens {
+        tokens: Vec<Snip20>,
     },
     IncentivizedToken {
         token: Snip20,
     },
+    StartHeight {
+        height: u64,
+    },
     EndHeight {
         height: u64,
     },
     RewardPoolBalance {
-        balance: Uint128,
+        balances: Vec<RewardAmount>,
+    },
+    RewardDust {
+        dust: Vec<RewardAmount>,
+    },
+    RewardSchedules {
+        schedules: Vec<RewardScheduleInfo>,
+        last_reward_block: u64,
+    },
+    AllowedFunders {
+        funders: Option<Vec<HumanAddr>>,
+    },
+    FundingHistory {
+        fundings: Vec<FundingInfo>,
+        total: u32,
     },
 
     QueryError {
@@ -152,6 +403,39 @@ pub enum QueryAnswer {
     },
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RewardAmount {
+    pub token: HumanAddr,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RoleInfo {
+    pub role: Role,
+    pub addresses: Vec<HumanAddr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RewardScheduleInfo {
+    pub start: u64,
+    pub end: u64,
+    pub rewards: Vec<RewardAmount>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FundingInfo {
+    pub funder: HumanAddr,
+    pub token: HumanAddr,
+    pub amount: Uint128,
+    pub block: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct UnbondingInfo {
+    pub amount: Uint128,
+    pub release_height: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum ResponseStatus {
@@ -0,0 +1,174 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    to_vec, Api, Binary, CanonicalAddr, HumanAddr, ReadonlyStorage, StdResult, Uint128,
+};
+use cosmwasm_storage::ReadonlyPrefixedStorage;
+use ripemd160::{Digest, Ripemd160};
+use secret_toolkit::crypto::secp256k1::{PublicKey, Signature};
+use secret_toolkit::crypto::sha_256;
+
+use crate::constants::REVOKED_PERMITS_KEY;
+use crate::error::ContractError;
+
+/// A query permit, as described in SNIP-24. It is signed offline by the user's wallet, so no transaction
+/// is needed to authenticate queries
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Permit {
+    pub params: PermitParams,
+    pub signature: PermitSignature,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitParams {
+    pub allowed_tokens: Vec<HumanAddr>,
+    pub permit_name: String,
+    pub chain_id: String,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitSignature {
+    pub pub_key: PubKey,
+    pub signature: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PubKey {
+    /// Ignored, but must be "tendermint/PubKeySecp256k1" for the wallet to sign it
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Secp256k1 public key, in its compressed form
+    pub value: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Allowance,
+    Balance,
+    Rewards,
+    Deposit,
+    Unbondings,
+    /// Allows every query
+    Owner,
+}
+
+impl Permit {
+    pub fn check_permission(&self, permission: &Permission) -> bool {
+        self.params.permissions.contains(permission)
+            || self.params.permissions.contains(&Permission::Owner)
+    }
+
+    /// Verifies the permit and returns the address that signed it
+    pub fn validate<S: ReadonlyStorage, A: Api>(
+        &self,
+        storage: &S,
+        api: &A,
+        contract_address: &HumanAddr,
+    ) -> StdResult<HumanAddr> {
+        if !self.params.allowed_tokens.contains(contract_address) {
+            return Err(ContractError::PermitNotForContract {
+                contract_address: contract_address.clone(),
+            }
+            .into());
+        }
+
+        let pub_key = self.signature.pub_key.value.as_slice();
+        let address = api.human_address(&CanonicalAddr(Binary(
+            Ripemd160::digest(&sha_256(pub_key)).to_vec(),
+        )))?;
+
+        let revoked_store = ReadonlyPrefixedStorage::multilevel(
+            &[REVOKED_PERMITS_KEY, address.0.as_bytes()],
+            storage,
+        );
+        if revoked_store
+            .get(self.params.permit_name.as_bytes())
+            .is_some()
+        {
+            return Err(ContractError::PermitRevoked {
+                permit_name: self.params.permit_name.clone(),
+                address,
+            }
+            .into());
+        }
+
+        let signed_bytes = to_vec(&SignedPermit::from_params(&self.params))?;
+        let signature = Signature::parse_slice(self.signature.signature.as_slice())?;
+        if !PublicKey::parse(pub_key)?.verify(&sha_256(&signed_bytes), signature) {
+            return Err(ContractError::InvalidPermitSignature {}.into());
+        }
+
+        Ok(address)
+    }
+}
+
+// The document that wallets actually sign. Field order matters, as wallets sign the JSON with sorted keys
+
+#[derive(Serialize)]
+struct SignedPermit {
+    account_number: Uint128,
+    chain_id: String,
+    fee: Fee,
+    memo: String,
+    msgs: Vec<PermitMsg>,
+    sequence: Uint128,
+}
+
+#[derive(Serialize)]
+struct Fee {
+    amount: Vec<FeeCoin>,
+    gas: Uint128,
+}
+
+#[derive(Serialize)]
+struct FeeCoin {
+    amount: Uint128,
+    denom: String,
+}
+
+#[derive(Serialize)]
+struct PermitMsg {
+    #[serde(rename = "type")]
+    kind: String,
+    value: PermitContent,
+}
+
+#[derive(Serialize)]
+struct PermitContent {
+    allowed_tokens: Vec<HumanAddr>,
+    permissions: Vec<Permission>,
+    permit_name: String,
+}
+
+impl SignedPermit {
+    fn from_params(params: &PermitParams) -> Self {
+        Self {
+            account_number: Uint128(0),
+            chain_id: params.chain_id.clone(),
+            fee: Fee {
+                amount: vec![FeeCoin {
+                    amount: Uint128(0),
+                    denom: "uscrt".to_string(),
+                }],
+                gas: Uint128(1),
+            },
+            memo: String::new(),
+            msgs: vec![PermitMsg {
+                kind: "query_permit".to_string(),
+                value: PermitContent {
+                    allowed_tokens: params.allowed_tokens.clone(),
+                    permissions: params.permissions.clone(),
+                    permit_name: params.permit_name.clone(),
+                },
+            }],
+            sequence: Uint128(0),
+        }
+    }
+}
@@ -2,10 +2,134 @@ use cosmwasm_std::HumanAddr;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::constants::BOOST_SCALE;
+
+/// UserInfo is a struct that keeps track of a single user's lockup
+///
+/// `locked` - Amount of the incentivized token locked by the user. This number is not scaled, so the user always
+///  gets back exactly what they deposited.
+/// `shares` - Weighted share of the pool, which is `locked` scaled down by `Config::inc_token_scale` and
+///  multiplied by `boost`. Rewards are distributed according to this number.
+/// `unlock_height` - The block from which `locked` can be redeemed.
+/// `boost` - Reward multiplier for locking the tokens, scaled up by `constants::BOOST_SCALE`. It is reset once
+///  the position is changed after `unlock_height`.
+/// `debt` - Reward debt of the user for every reward token. The order is the same as `Config::reward_tokens`.
+/// `auto_compound` - Whether the user opted in to have their rewards restaked by anyone, when the incentivized
+///  token is also a reward token. Only users with locked tokens can opt in.
+/// `frozen_rewards` - Rewards earned before an emergency redeem or a kick, for every reward token. They are paid
+///  out with the next claim, or earlier by the treasury.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UserInfo {
     pub locked: u128,
-    pub debt: u128,
+    pub shares: u128,
+    pub unlock_height: u64,
+    pub boost: u64,
+    pub debt: Vec<u128>,
+    pub auto_compound: bool,
+    pub frozen_rewards: Vec<u128>,
+}
+
+impl UserInfo {
+    pub fn new(reward_tokens: usize) -> Self {
+        UserInfo {
+            locked: 0,
+            shares: 0,
+            unlock_height: 0,
+            boost: BOOST_SCALE,
+            debt: vec![0; reward_tokens],
+            auto_compound: false,
+            frozen_rewards: vec![0; reward_tokens],
+        }
+    }
+}
+
+/// Unbonding is a redeemed amount that is waiting for `Config::unbonding_period` to pass
+///
+/// `amount` - Amount of the incentivized token to be paid out.
+/// `release_height` - The block from which `amount` can be claimed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Unbonding {
+    pub amount: u128,
+    pub release_height: u64,
+}
+
+/// Allowance is what a spender may transfer of an owner's locked tokens, as in SNIP-20
+///
+/// `amount` - Amount of locked tokens left to spend.
+/// `expiration` - The time, in seconds since the epoch, from which the allowance can't be spent anymore.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Allowance {
+    pub amount: u128,
+    pub expiration: Option<u64>,
+}
+
+/// ContractStatus is the level to which the contract is paused
+///
+/// `Normal` - Everything is allowed.
+/// `DepositsPaused` - Nothing can be locked, including compounded rewards. Redeeming and claiming still work.
+/// `RewardsPaused` - Everything is allowed, but no rewards are distributed. Schedules are pushed forward by the
+///  paused blocks, as if nothing was locked.
+/// `EmergencyOnly` - Rewards are paused, and besides viewing keys and permits only `EmergencyRedeem` is
+///  allowed.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, Copy, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractStatus {
+    Normal,
+    DepositsPaused,
+    RewardsPaused,
+    EmergencyOnly,
+}
+
+impl ContractStatus {
+    pub fn distributes_rewards(self) -> bool {
+        match self {
+            ContractStatus::Normal | ContractStatus::DepositsPaused => true,
+            ContractStatus::RewardsPaused | ContractStatus::EmergencyOnly => false,
+        }
+    }
+
+    pub fn accepts_deposits(self) -> bool {
+        match self {
+            ContractStatus::Normal | ContractStatus::RewardsPaused => true,
+            ContractStatus::DepositsPaused | ContractStatus::EmergencyOnly => false,
+        }
+    }
+}
+
+/// Role is a set of admin actions that can be granted to several addresses
+///
+/// `Pauser` - Can change the contract's status.
+/// `CampaignManager` - Can add reward schedules, and move the deadline and the start block of the reward campaigns.
+/// `Treasury` - Can claim the reward pool and pay out users' frozen rewards.
+/// `RoleAdmin` - Can grant and revoke roles. `Config::admin` is always allowed to do that as well.
+///
+/// The creator of the contract gets every role. Accepting the admin role moves the old admin's roles to the new
+/// admin.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, Copy, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Pauser,
+    CampaignManager,
+    Treasury,
+    RoleAdmin,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [
+        Role::Pauser,
+        Role::CampaignManager,
+        Role::Treasury,
+        Role::RoleAdmin,
+    ];
+
+    pub fn storage_key(self) -> &'static [u8] {
+        match self {
+            Role::Pauser => b"pauser",
+            Role::CampaignManager => b"campaignmanager",
+            Role::Treasury => b"treasury",
+            Role::RoleAdmin => b"roleadmin",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, JsonSchema)]
@@ -14,31 +138,103 @@ pub struct Snip20 {
     pub contract_hash: String,
 }
 
+/// Config is a struct that keeps the pool's settings
+///
+/// `pending_admin` - Address that was proposed as the next admin, and has yet to accept it.
+/// `contract_address` - Address of this contract. Queries don't get it, and permits have to be checked against it.
+/// `inc_token_scale` - Deposits of the incentivized token are divided by this number to get the amount of
+///  shares. Should fit the token's decimals, so that a single share is still worth a meaningful amount.
+/// `reward_scale` - `TokenRewards::acc_reward_per_share` is scaled up by this number to keep precision.
+/// `max_lock_blocks` - The longest lockup a deposit can ask for.
+/// `max_boost` - Reward multiplier for a lockup of `max_lock_blocks`, scaled up by `constants::BOOST_SCALE`.
+///  Shorter lockups get a linearly smaller multiplier.
+/// `unbonding_period` - Amount of blocks redeemed tokens wait before they can be claimed. If zero, tokens are
+///  sent back immediately.
+/// `status` - Which actions are currently allowed, see `ContractStatus`.
+/// `start_block` - No rewards are distributed before this block, even if a schedule starts earlier.
+/// `allowed_funders` - Addresses that may deposit rewards. If `None`, anyone can.
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
 pub struct Config {
     pub admin: HumanAddr,
-    pub reward_token: Snip20,
+    pub pending_admin: Option<HumanAddr>,
+    pub contract_address: HumanAddr,
+    pub reward_tokens: Vec<Snip20>,
     pub inc_token: Snip20,
+    pub inc_token_scale: u128,
+    pub reward_scale: u128,
+    pub max_lock_blocks: u64,
+    pub max_boost: u64,
+    pub unbonding_period: u64,
+    pub start_block: u64,
     pub pool_claim_block: u64,
-    pub deadline: u64,
     pub viewing_key: String,
     pub prng_seed: Vec<u8>,
-    pub is_stopped: bool,
+    pub status: ContractStatus,
+    pub allowed_funders: Option<Vec<HumanAddr>>,
 }
 
 /// RewardPool is a struct that keeps track of rewards and lockups
 ///
-/// `pending_rewards` - Rewards left to distribute.
-/// `inc_token_supply` - Total supply of the incentivized token that is locked in the contract.
-///  This number is scaled down by `constants::INC_TOKEN_SCALE`. Keeping track of it so external query will not
-///  be necessary every time a user locks/redeems tokens.
+/// `inc_token_supply` - Total weighted shares of the incentivized token that is locked in the contract. This is
+///  the sum of every user's `UserInfo::shares`. Keeping track of it so external query will not be necessary every time
+///  a user locks/redeems tokens.
+/// `inc_token_locked` - Total `UserInfo::locked`, which is the principal held for the users.
+/// `inc_token_unbonding` - Total amount of the incentivized token that is unbonding and wasn't claimed yet.
 /// `last_reward_block` - Last block in which rewards got updated.
-/// `acc_reward_per_share` - Accumulated rewards per share. This number is scaled up by `constants::REWARD_SCALE`
-///  and shares scaled the same way as `inc_token_supply`.
+/// `rewards` - Distribution state of every reward token. The order is the same as `Config::reward_tokens`.
+/// `schedules` - Reward schedules that haven't been fully distributed yet, ordered by their start. The schedule
+///  that ends last is always kept, so it can be topped up or extended after it ends. Other schedules move what
+///  they didn't distribute to it when they end.
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
 pub struct RewardPool {
-    pub pending_rewards: u128,
     pub inc_token_supply: u128,
+    pub inc_token_locked: u128,
+    pub inc_token_unbonding: u128,
     pub last_reward_block: u64,
+    pub rewards: Vec<TokenRewards>,
+    pub schedules: Vec<RewardSchedule>,
+}
+
+/// TokenRewards is a struct that keeps track of the distribution of a single reward token
+///
+/// `pending_rewards` - Rewards left to distribute. This is the sum of the token's amounts in all schedules.
+/// `acc_reward_per_share` - Accumulated rewards per share. This number is scaled up by `Config::reward_scale`
+///  and shares scaled the same way as `RewardPool::inc_token_supply`.
+/// `dust` - Distributed rewards that no share got because of rounding, scaled up by `Config::reward_scale`.
+///  Whole tokens are added to the next deposit of the token, or swept by the treasury.
+/// `unclaimed` - Distributed rewards that weren't paid to the users yet, including frozen rewards. This number is
+///  scaled up by `Config::reward_scale`.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
+pub struct TokenRewards {
+    pub pending_rewards: u128,
     pub acc_reward_per_share: u128,
+    pub dust: u128,
+    pub unclaimed: u128,
+}
+
+/// Funding is a single deposit of rewards
+///
+/// `funder` - Address that sent the rewards.
+/// `token` - The reward token that was deposited.
+/// `amount` - Amount of `token` that was received.
+/// `block` - The block in which the rewards were deposited.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
+pub struct Funding {
+    pub funder: HumanAddr,
+    pub token: HumanAddr,
+    pub amount: u128,
+    pub block: u64,
+}
+
+/// RewardSchedule is a struct that keeps track of a single reward campaign
+///
+/// `start` - The block from which rewards are distributed.
+/// `end` - The block in which the last rewards are distributed.
+/// `amounts` - Rewards left to distribute in this schedule for every reward token. The order is the same as
+///  `Config::reward_tokens`. They are spread linearly over the blocks left until `end`.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
+pub struct RewardSchedule {
+    pub start: u64,
+    pub end: u64,
+    pub amounts: Vec<u128>,
 }
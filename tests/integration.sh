@@ -389,11 +389,11 @@ function redeem() {
         log "successfully redeemed ${amount} for \"$key\""
     elif ! redeem_tx="$(wait_for_compute_tx "$tx_hash" "waiting for redeem from \"$key\" to process")">/dev/null; then
         redeem_error="$(get_generic_err "$redeem_tx")"
-        if ! jq -Re 'startswith("insufficient funds to redeem")' <<< "$redeem_error">/dev/null; then
+        if ! jq -Re 'fromjson? | .code == "insufficient_funds"' <<< "$redeem_error">/dev/null; then
             log "$redeem_error"
             return 1
         fi
-#        assert_eq "$redeem_error" "$(pad_space "insufficient funds to redeem: balance=$old_balance, required=$amount")"
+#        assert_eq "$redeem_error" "$(pad_space "{\"code\":\"insufficient_funds\",\"msg\":\"insufficient funds to redeem: balance=$old_balance, required=$amount\"}")"
     fi
 
 #    assert_eq "$redeem_response" "$(pad_space '{"redeem":{"status":"success"}}')"
@@ -461,7 +461,10 @@ function test_deposit() {
         ! redeem_response="$(wait_for_compute_tx "$tx_hash" "waiting for overdraft from \"$key\" to process")"
         log "trying to overdraft from \"$key\" was rejected"
         assert_eq \
-            "$(get_generic_err "$redeem_response")" \
+            "$(get_generic_err "$redeem_response" | jq -r '.code')" \
+            'insufficient_funds'
+        assert_eq \
+            "$(get_generic_err "$redeem_response" | jq -r '.msg')" \
             "insufficient funds to redeem: balance=${deposits[$key]}, required=$overdraft"
     done
 
@@ -663,7 +666,7 @@ function main() {
     # Rewards init
     deadline=$(query_height)
     deadline=$(($deadline + 100)) # Will run for approximately ~10 minutes
-    init_msg='{"reward_token":{"address":"'"$scrt_contract_addr"'", "contract_hash":"'"$scrt_contract_hash"'"}, "inc_token":{"address":"'"$eth_contract_addr"'", "contract_hash":"'"$eth_contract_hash"'"}, "deadline":'"$deadline"', "pool_claim_block":'"$deadline"', "viewing_key": "123", "prng_seed": "'"$prng_seed"'"}'
+    init_msg='{"reward_tokens":[{"address":"'"$scrt_contract_addr"'", "contract_hash":"'"$scrt_contract_hash"'"}], "inc_token":{"address":"'"$eth_contract_addr"'", "contract_hash":"'"$eth_contract_hash"'"}, "inc_token_scale":"1000000000000", "reward_scale":"1000000000000", "max_lock_blocks":0, "max_boost":10000, "unbonding_period":0, "start_block":0, "deadline":'"$deadline"', "pool_claim_block":'"$deadline"', "viewing_key": "123", "prng_seed": "'"$prng_seed"'"}'
     lockup_contract_addr="$(create_contract '.' "$init_msg")"
     lockup_contract_hash="$(secretcli q compute contract-hash "$lockup_contract_addr")"
     lockup_contract_hash="${lockup_contract_hash:2}"